@@ -0,0 +1,166 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use lila_gif::{
+    Renderer, Themes,
+    api::{GridRequest, Player, RequestBody, RequestFrame, Title},
+    assets::{BoardTheme, PieceSet},
+    bench::{BoardView, RenderFrame, render_bar, render_caption_region, render_diff},
+    validate::validate,
+};
+use ndarray::ArrayViewMut2;
+use serde_json::json;
+use shakmaty::{fen::Fen, uci::UciMove};
+
+const THEMES: [(BoardTheme, PieceSet); 4] = [
+    (BoardTheme::Brown, PieceSet::Cburnett),
+    (BoardTheme::Blue, PieceSet::Merida),
+    (BoardTheme::Green, PieceSet::Pixel),
+    (BoardTheme::Purple, PieceSet::Xkcd),
+];
+
+const FEN: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+
+fn name(board: BoardTheme, piece: PieceSet) -> String {
+    format!("{board:?}-{piece:?}").to_lowercase()
+}
+
+fn image(themes: &Themes) -> Renderer<'_> {
+    Renderer::new(themes)
+        .position(FEN.parse().expect("fen"))
+        .last_move("b8c6".parse().expect("uci"))
+}
+
+fn player() -> Player {
+    Player {
+        name: "Magnus Carlsen".parse().expect("name"),
+        title: Some(Title::Gm),
+        rating: Some(2863),
+        rating_diff: Some(-7),
+        tag: Some("NO".parse().expect("tag")),
+    }
+}
+
+fn bars(themes: &Themes) -> Renderer<'_> {
+    image(themes).white(player()).black(player())
+}
+
+fn game(themes: &Themes) -> Renderer<'_> {
+    Renderer::new(themes).game(RequestBody::example())
+}
+
+fn grid(themes: &Themes) -> Renderer<'_> {
+    let grid: GridRequest = serde_json::from_value(json!({
+        "boards": (1..=4)
+            .map(|i| json!({ "fen": FEN, "lastMove": "b8c6", "caption": format!("{i}. Nc6") }))
+            .collect::<Vec<_>>(),
+    }))
+    .expect("grid request");
+    Renderer::new(themes).grid(grid)
+}
+
+fn frame(fen: &str, last_move: Option<&str>) -> RenderFrame {
+    RenderFrame::new(&RequestFrame {
+        fen: fen.parse::<Fen>().expect("fen"),
+        last_move: last_move.map(|uci| uci.parse::<UciMove>().expect("uci")),
+        ..RequestFrame::default()
+    })
+}
+
+/// Benchmarks a request for each theme, with throughput in bytes of output
+/// per frame.
+fn bench_request(
+    c: &mut Criterion,
+    themes: &Themes,
+    group_name: &str,
+    request: fn(&Themes) -> Renderer<'_>,
+) {
+    let mut group = c.benchmark_group(group_name);
+    group.sample_size(20);
+    for (board, piece) in THEMES {
+        let render = || request(themes).theme(board).piece(piece);
+        let gif = render().to_vec().expect("gif");
+        let frames = validate(&gif).expect("valid gif").images;
+        group.throughput(Throughput::Bytes((gif.len() / frames) as u64));
+        group.bench_function(name(board, piece), |b| {
+            b.iter_batched(
+                render,
+                |renderer| black_box(renderer.to_vec()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let themes = Themes::new();
+    bench_request(c, &themes, "image", image);
+    bench_request(c, &themes, "bars", bars);
+    bench_request(c, &themes, "game", game);
+    bench_request(c, &themes, "grid", grid);
+
+    let font = themes.font();
+
+    let prev = frame(FEN, Some("b8c6"));
+    let next = frame(
+        "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+        Some("f1b5"),
+    );
+    let mut group = c.benchmark_group("render_diff");
+    for (board, piece) in THEMES {
+        let theme = themes.get(board, piece);
+        let mut buffer = vec![0; theme.width() * theme.width()];
+        group.bench_function(name(board, piece), |b| {
+            b.iter(|| {
+                render_diff(
+                    &mut buffer,
+                    theme,
+                    BoardView::default(),
+                    Some(black_box(&prev)),
+                    black_box(&next),
+                    font,
+                )
+            })
+        });
+    }
+    group.finish();
+
+    let player = player();
+    let mut group = c.benchmark_group("render_bar");
+    for (board, piece) in THEMES {
+        let theme = themes.get(board, piece);
+        let mut buffer = vec![0; theme.bar_height() * theme.width()];
+        group.bench_function(name(board, piece), |b| {
+            b.iter(|| {
+                let view =
+                    ArrayViewMut2::from_shape((theme.bar_height(), theme.width()), &mut buffer)
+                        .expect("bar shape");
+                render_bar(view, theme, font, black_box(&player))
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("text");
+    for (board, piece) in THEMES {
+        let theme = themes.get(board, piece);
+        let mut buffer = vec![0; theme.bar_height() * theme.width()];
+        group.bench_function(format!("caption/{}", name(board, piece)), |b| {
+            b.iter(|| {
+                render_caption_region(
+                    &mut buffer,
+                    theme,
+                    font,
+                    black_box("23. Nxf7+ Kxf7"),
+                    0,
+                    theme.width(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+#![no_main]
+
+use std::sync::LazyLock;
+
+use axum::Json;
+use libfuzzer_sys::fuzz_target;
+use lila_gif::{Render, Themes, api::RequestBody, validate::validate};
+
+static THEMES: LazyLock<Themes> = LazyLock::new(Themes::new);
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(Json(req)) = Json::<RequestBody>::from_bytes(data) else {
+        return;
+    };
+    let gif: Vec<u8> = Render::new_animation(&THEMES, req).flatten().collect();
+    if let Err(err) = validate(&gif) {
+        panic!("invalid gif: {err}");
+    }
+});
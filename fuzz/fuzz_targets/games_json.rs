@@ -0,0 +1,22 @@
+#![no_main]
+
+use std::sync::LazyLock;
+
+use axum::Json;
+use libfuzzer_sys::fuzz_target;
+use lila_gif::{GridRender, Themes, api::GridAnimationRequest, validate::validate};
+
+static THEMES: LazyLock<Themes> = LazyLock::new(Themes::new);
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(Json(req)) = Json::<GridAnimationRequest>::from_bytes(data) else {
+        return;
+    };
+    let Ok(render) = GridRender::new_animation(&THEMES, req) else {
+        return;
+    };
+    let gif: Vec<u8> = render.flatten().collect();
+    if let Err(err) = validate(&gif) {
+        panic!("invalid gif: {err}");
+    }
+});
@@ -0,0 +1,25 @@
+#![no_main]
+
+use std::sync::LazyLock;
+
+use axum::{extract::Query, http::Uri};
+use libfuzzer_sys::fuzz_target;
+use lila_gif::{Render, Themes, api::RequestParams, validate::validate};
+
+static THEMES: LazyLock<Themes> = LazyLock::new(Themes::new);
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(query) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(uri) = format!("/image.gif?{query}").parse::<Uri>() else {
+        return;
+    };
+    let Ok(Query(req)) = Query::<RequestParams>::try_from_uri(&uri) else {
+        return;
+    };
+    let gif: Vec<u8> = Render::new_image(&THEMES, req).flatten().collect();
+    if let Err(err) = validate(&gif) {
+        panic!("invalid gif for {query:?}: {err}");
+    }
+});
@@ -1,16 +1,17 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
-use arrayvec::ArrayString;
+use arrayvec::{ArrayString, ArrayVec};
 use serde::{Deserialize, de};
 use serde_with::{DisplayFromStr, serde_as};
 use shakmaty::{
-    CastlingMode, Chess, EnPassantMode, Position, Setup, Square, fen::Fen, san::SanPlus,
-    uci::UciMove,
+    Bitboard, CastlingMode, Chess, Color, EnPassantMode, File, KnownOutcome, Piece, Position, Rank,
+    Setup, Square, fen::Fen, san::SanPlus, uci::UciMove,
 };
 
 use crate::assets::{BoardTheme, PieceSet};
 
 #[derive(Deserialize, Debug, Default, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Orientation {
     #[serde(rename = "white")]
     #[default]
@@ -27,17 +28,223 @@ impl Orientation {
         }
     }
 
-    pub fn x(self, square: Square) -> usize {
-        self.fold(usize::from(square.file()), 7 - usize::from(square.file()))
+    pub fn x(self, region: Region, square: Square) -> usize {
+        let file = usize::from(square.file());
+        self.fold(
+            file - usize::from(region.min_file),
+            usize::from(region.max_file) - file,
+        )
     }
 
-    pub fn y(self, square: Square) -> usize {
-        self.fold(7 - usize::from(square.rank()), usize::from(square.rank()))
+    pub fn y(self, region: Region, square: Square) -> usize {
+        let rank = usize::from(square.rank());
+        self.fold(
+            usize::from(region.max_rank) - rank,
+            rank - usize::from(region.min_rank),
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub min_file: File,
+    pub max_file: File,
+    pub min_rank: Rank,
+    pub max_rank: Rank,
+}
+
+impl Default for Region {
+    fn default() -> Region {
+        Region {
+            min_file: File::A,
+            max_file: File::H,
+            min_rank: Rank::First,
+            max_rank: Rank::Eighth,
+        }
+    }
+}
+
+impl Region {
+    pub fn files(self) -> usize {
+        usize::from(self.max_file) - usize::from(self.min_file) + 1
+    }
+
+    pub fn ranks(self) -> usize {
+        usize::from(self.max_rank) - usize::from(self.min_rank) + 1
+    }
+
+    pub fn squares(self) -> Bitboard {
+        let files = File::ALL
+            .into_iter()
+            .filter(|&file| self.min_file <= file && file <= self.max_file)
+            .map(Bitboard::from_file)
+            .fold(Bitboard::EMPTY, |acc, bb| acc | bb);
+        let ranks = Rank::ALL
+            .into_iter()
+            .filter(|&rank| self.min_rank <= rank && rank <= self.max_rank)
+            .map(Bitboard::from_rank)
+            .fold(Bitboard::EMPTY, |acc, bb| acc | bb);
+        files & ranks
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidRegion;
+
+impl fmt::Display for InvalidRegion {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("invalid region, expected two corner squares like a1:d4")
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{}:{}",
+            Square::from_coords(self.min_file, self.min_rank),
+            Square::from_coords(self.max_file, self.max_rank)
+        )
+    }
+}
+
+impl FromStr for Region {
+    type Err = InvalidRegion;
+
+    fn from_str(s: &str) -> Result<Region, InvalidRegion> {
+        let (a, b) = s.split_once(':').ok_or(InvalidRegion)?;
+        let a: Square = a.parse().map_err(|_| InvalidRegion)?;
+        let b: Square = b.parse().map_err(|_| InvalidRegion)?;
+        Ok(Region {
+            min_file: a.file().min(b.file()),
+            max_file: a.file().max(b.file()),
+            min_rank: a.rank().min(b.rank()),
+            max_rank: a.rank().max(b.rank()),
+        })
     }
 }
 
 pub type PlayerName = ArrayString<100>; // length limited to prevent dos
 
+pub type PlayerTag = ArrayString<30>; // country code or team name
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum Title {
+    Gm,
+    Wgm,
+    Im,
+    Wim,
+    Fm,
+    Wfm,
+    Nm,
+    Cm,
+    Wcm,
+    Wnm,
+    Lm,
+    Bot,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Player {
+    pub name: PlayerName,
+    pub title: Option<Title>,
+    pub rating: Option<u16>,
+    pub rating_diff: Option<i16>,
+    pub tag: Option<PlayerTag>,
+}
+
+impl Player {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty()
+            && self.title.is_none()
+            && self.rating.is_none()
+            && self.rating_diff.is_none()
+            && self.tag.is_none()
+    }
+
+    fn with_details(
+        player: Option<Player>,
+        title: Option<Title>,
+        rating: Option<u16>,
+        rating_diff: Option<i16>,
+        tag: Option<PlayerTag>,
+    ) -> Option<Player> {
+        if title.is_none() && rating.is_none() && rating_diff.is_none() && tag.is_none() {
+            return player;
+        }
+        let player = player.unwrap_or_default();
+        Some(Player {
+            title: title.or(player.title),
+            rating: rating.or(player.rating),
+            rating_diff: rating_diff.or(player.rating_diff),
+            tag: tag.or(player.tag),
+            ..player
+        })
+    }
+}
+
+/// Player details, as an alternative to a plain name.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlayerInfo {
+    #[serde(default)]
+    name: PlayerName,
+    title: Option<Title>,
+    rating: Option<u16>,
+    rating_diff: Option<i16>,
+    tag: Option<PlayerTag>,
+}
+
+impl<'de> Deserialize<'de> for Player {
+    fn deserialize<D>(deseralizer: D) -> Result<Player, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct PlayerVisitor;
+
+        impl<'de> de::Visitor<'de> for PlayerVisitor {
+            type Value = Player;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("player name or object")
+            }
+
+            fn visit_str<E>(self, name: &str) -> Result<Player, E>
+            where
+                E: de::Error,
+            {
+                match PlayerName::from(name) {
+                    Ok(name) => Ok(Player {
+                        name,
+                        ..Player::default()
+                    }),
+                    Err(_) => Err(de::Error::custom("player name too long")),
+                }
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Player, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let info = PlayerInfo::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(Player {
+                    name: info.name,
+                    title: info.title,
+                    rating: info.rating,
+                    rating_diff: info.rating_diff,
+                    tag: info.tag,
+                })
+            }
+        }
+
+        deseralizer.deserialize_any(PlayerVisitor)
+    }
+}
+
 pub type Comment = ArrayString<255>; // strict length limit for gif comments
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -48,6 +255,18 @@ pub enum CheckSquare {
     Square(Square),
 }
 
+impl CheckSquare {
+    /// Accepted strings other than square names.
+    pub(crate) const NAMES: [(&'static str, CheckSquare); 6] = [
+        ("1", CheckSquare::Yes),
+        ("yes", CheckSquare::Yes),
+        ("true", CheckSquare::Yes),
+        ("0", CheckSquare::No),
+        ("no", CheckSquare::No),
+        ("false", CheckSquare::No),
+    ];
+}
+
 impl<'de> Deserialize<'de> for CheckSquare {
     fn deserialize<D>(deseralizer: D) -> Result<CheckSquare, D::Error>
     where
@@ -66,10 +285,8 @@ impl<'de> Deserialize<'de> for CheckSquare {
             where
                 E: de::Error,
             {
-                if name == "1" || name == "yes" || name == "true" {
-                    Ok(CheckSquare::Yes)
-                } else if name == "0" || name == "no" || name == "false" {
-                    Ok(CheckSquare::No)
+                if let Some(&(_, check)) = CheckSquare::NAMES.iter().find(|(n, _)| *n == name) {
+                    Ok(check)
                 } else {
                     match name.parse() {
                         Ok(sq) => Ok(CheckSquare::Square(sq)),
@@ -93,11 +310,287 @@ impl<'de> Deserialize<'de> for CheckSquare {
     }
 }
 
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, strum::EnumString)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum HighlightColor {
+    Green = 0,
+    Red = 1,
+    Blue = 2,
+    Yellow = 3,
+}
+
+impl HighlightColor {
+    pub const ALL: [HighlightColor; 4] = [
+        HighlightColor::Green,
+        HighlightColor::Red,
+        HighlightColor::Blue,
+        HighlightColor::Yellow,
+    ];
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Highlights {
+    by_color: [Bitboard; 4],
+}
+
+impl Highlights {
+    pub fn set(&mut self, sq: Square, color: HighlightColor) {
+        for squares in &mut self.by_color {
+            squares.discard(sq);
+        }
+        self.by_color[color as usize].add(sq);
+    }
+
+    pub fn get(&self, sq: Square) -> Option<HighlightColor> {
+        HighlightColor::ALL
+            .into_iter()
+            .find(|&color| self.by_color[color as usize].contains(sq))
+    }
+
+    pub fn diff(&self, other: &Highlights) -> Bitboard {
+        self.by_color
+            .iter()
+            .zip(&other.by_color)
+            .fold(Bitboard::EMPTY, |acc, (a, b)| acc | (*a ^ *b))
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidHighlights;
+
+impl fmt::Display for InvalidHighlights {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("invalid highlights, expected squares with colors like e4:red,d5:blue")
+    }
+}
+
+impl FromStr for Highlights {
+    type Err = InvalidHighlights;
+
+    fn from_str(s: &str) -> Result<Highlights, InvalidHighlights> {
+        let mut highlights = Highlights::default();
+        for part in s.split(',').filter(|part| !part.is_empty()) {
+            let (sq, color) = part.split_once(':').ok_or(InvalidHighlights)?;
+            highlights.set(
+                sq.parse().map_err(|_| InvalidHighlights)?,
+                color.parse().map_err(|_| InvalidHighlights)?,
+            );
+        }
+        Ok(highlights)
+    }
+}
+
+impl<'de> Deserialize<'de> for Highlights {
+    fn deserialize<D>(deseralizer: D) -> Result<Highlights, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct HighlightsVisitor;
+
+        impl<'de> de::Visitor<'de> for HighlightsVisitor {
+            type Value = Highlights;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("map from square names to colors, or string like e4:red,d5:blue")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Highlights, E>
+            where
+                E: de::Error,
+            {
+                s.parse().map_err(de::Error::custom)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Highlights, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut highlights = Highlights::default();
+                while let Some((sq, color)) = map.next_entry::<String, HighlightColor>()? {
+                    highlights.set(
+                        sq.parse()
+                            .map_err(|_| de::Error::custom("invalid square name"))?,
+                        color,
+                    );
+                }
+                Ok(highlights)
+            }
+        }
+
+        deseralizer.deserialize_any(HighlightsVisitor)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ghost {
+    pub piece: Piece,
+    pub square: Square,
+}
+
+#[derive(Debug)]
+pub struct InvalidGhost;
+
+impl fmt::Display for InvalidGhost {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("invalid ghost, expected piece and square like Nf3 or pe5")
+    }
+}
+
+impl fmt::Display for Ghost {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}{}", self.piece.char(), self.square)
+    }
+}
+
+impl FromStr for Ghost {
+    type Err = InvalidGhost;
+
+    fn from_str(s: &str) -> Result<Ghost, InvalidGhost> {
+        let mut chars = s.chars();
+        let piece = chars
+            .next()
+            .and_then(Piece::from_char)
+            .ok_or(InvalidGhost)?;
+        Ok(Ghost {
+            piece,
+            square: chars.as_str().parse().map_err(|_| InvalidGhost)?,
+        })
+    }
+}
+
+/// Squares to show through fog, as a bitboard with bit 0 for a1 and bit 63
+/// for h8.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VisibleSquares(pub Bitboard);
+
+impl VisibleSquares {
+    /// Largest integer that JSON numbers represent exactly in JavaScript.
+    pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+    const NOT_EXACT: &'static str = "visible squares above 2^53 are not exact as numbers, pass them as a string like \"0xffff000000000000\"";
+}
+
+#[derive(Debug)]
+pub struct InvalidVisibleSquares;
+
+impl fmt::Display for InvalidVisibleSquares {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("invalid visible squares, expected a 64 bit integer like 65535 or 0xffff")
+    }
+}
+
+impl FromStr for VisibleSquares {
+    type Err = InvalidVisibleSquares;
+
+    fn from_str(s: &str) -> Result<VisibleSquares, InvalidVisibleSquares> {
+        match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => s.parse(),
+        }
+        .map(|bits| VisibleSquares(Bitboard(bits)))
+        .map_err(|_| InvalidVisibleSquares)
+    }
+}
+
+impl<'de> Deserialize<'de> for VisibleSquares {
+    fn deserialize<D>(deseralizer: D) -> Result<VisibleSquares, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct VisibleSquaresVisitor;
+
+        impl de::Visitor<'_> for VisibleSquaresVisitor {
+            type Value = VisibleSquares;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("bitboard as integer or string")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<VisibleSquares, E>
+            where
+                E: de::Error,
+            {
+                s.parse().map_err(de::Error::custom)
+            }
+
+            fn visit_u64<E>(self, bits: u64) -> Result<VisibleSquares, E>
+            where
+                E: de::Error,
+            {
+                if bits > VisibleSquares::MAX_SAFE_INTEGER {
+                    return Err(de::Error::custom(VisibleSquares::NOT_EXACT));
+                }
+                Ok(VisibleSquares(Bitboard(bits)))
+            }
+
+            fn visit_i64<E>(self, bits: i64) -> Result<VisibleSquares, E>
+            where
+                E: de::Error,
+            {
+                u64::try_from(bits)
+                    .map_err(|_| de::Error::custom(InvalidVisibleSquares))
+                    .and_then(|bits| self.visit_u64(bits))
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<VisibleSquares, E>
+            where
+                E: de::Error,
+            {
+                Err(de::Error::custom(VisibleSquares::NOT_EXACT))
+            }
+        }
+
+        deseralizer.deserialize_any(VisibleSquaresVisitor)
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Blindfold {
+    #[default]
+    Off,
+    All,
+    Discs,
+    White,
+    Black,
+}
+
+impl Blindfold {
+    pub fn hides(self, color: Color) -> bool {
+        match self {
+            Blindfold::Off => false,
+            Blindfold::All | Blindfold::Discs => true,
+            Blindfold::White => color.is_white(),
+            Blindfold::Black => color.is_black(),
+        }
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum Coordinates {
     No,
     #[default]
-    Yes,
+    Inside,
+    Outside,
+    All,
+}
+
+impl Coordinates {
+    /// Accepted strings.
+    pub(crate) const NAMES: [(&'static str, Coordinates); 9] = [
+        ("1", Coordinates::Inside),
+        ("yes", Coordinates::Inside),
+        ("true", Coordinates::Inside),
+        ("inside", Coordinates::Inside),
+        ("outside", Coordinates::Outside),
+        ("all", Coordinates::All),
+        ("0", Coordinates::No),
+        ("no", Coordinates::No),
+        ("false", Coordinates::No),
+    ];
 }
 
 impl<'de> Deserialize<'de> for Coordinates {
@@ -111,19 +604,19 @@ impl<'de> Deserialize<'de> for Coordinates {
             type Value = Coordinates;
 
             fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-                fmt.write_str("\"1\", \"yes\", \"true\", \"0\", \"no\", \"false\" or bool")
+                for (name, _) in Coordinates::NAMES {
+                    write!(fmt, "\"{name}\", ")?;
+                }
+                fmt.write_str("or bool")
             }
 
             fn visit_str<E>(self, name: &str) -> Result<Coordinates, E>
             where
                 E: de::Error,
             {
-                if name == "1" || name == "yes" || name == "true" {
-                    Ok(Coordinates::Yes)
-                } else if name == "0" || name == "no" || name == "false" {
-                    Ok(Coordinates::No)
-                } else {
-                    Err(de::Error::custom("invalid coordinates value"))
+                match Coordinates::NAMES.iter().find(|(n, _)| *n == name) {
+                    Some(&(_, coordinates)) => Ok(coordinates),
+                    None => Err(de::Error::custom("invalid coordinates value")),
                 }
             }
 
@@ -132,7 +625,7 @@ impl<'de> Deserialize<'de> for Coordinates {
                 E: de::Error,
             {
                 Ok(match yes {
-                    true => Coordinates::Yes,
+                    true => Coordinates::Inside,
                     false => Coordinates::No,
                 })
             }
@@ -153,11 +646,47 @@ impl CheckSquare {
 }
 
 #[derive(Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct FrameClock {
     pub white: Option<u32>,
     pub black: Option<u32>,
 }
 
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Trailer {
+    None,
+    #[default]
+    Dark,
+    Repeat,
+}
+
+pub type BannerText = ArrayString<100>; // length limited to prevent dos
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum GameStatus {
+    Mate,
+    Resign,
+    Stalemate,
+    Timeout,
+    Outoftime,
+    Draw,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GameResult {
+    #[serde_as(as = "DisplayFromStr")]
+    pub outcome: KnownOutcome,
+    pub status: Option<GameStatus>,
+    pub text: Option<BannerText>,
+    pub delay: Option<u16>,
+}
+
 #[derive(Debug, Copy, Clone, strum::EnumIter, strum::EnumString, strum::IntoStaticStr)]
 #[repr(u8)]
 pub enum MoveGlyph {
@@ -179,11 +708,37 @@ pub enum MoveGlyph {
     Zugzwang,
 }
 
+impl fmt::Display for MoveGlyph {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match *self {
+            MoveGlyph::Zugzwang => "⨀", // displayed as O
+            glyph => glyph.into(),
+        })
+    }
+}
+
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RequestParams {
-    pub white: Option<PlayerName>,
-    pub black: Option<PlayerName>,
+    pub white: Option<Player>,
+    pub black: Option<Player>,
+    #[serde(rename = "whiteTitle")]
+    pub white_title: Option<Title>,
+    #[serde(rename = "blackTitle")]
+    pub black_title: Option<Title>,
+    #[serde(rename = "whiteRating")]
+    pub white_rating: Option<u16>,
+    #[serde(rename = "blackRating")]
+    pub black_rating: Option<u16>,
+    #[serde(rename = "whiteRatingDiff")]
+    pub white_rating_diff: Option<i16>,
+    #[serde(rename = "blackRatingDiff")]
+    pub black_rating_diff: Option<i16>,
+    #[serde(rename = "whiteTag")]
+    pub white_tag: Option<PlayerTag>,
+    #[serde(rename = "blackTag")]
+    pub black_tag: Option<PlayerTag>,
     pub comment: Option<Comment>,
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default)]
@@ -194,6 +749,22 @@ pub struct RequestParams {
     #[serde(default)]
     pub check: CheckSquare,
     #[serde(default)]
+    pub highlights: Highlights,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub selected: Option<Square>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub premove: Option<UciMove>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub ghost: Option<Ghost>,
+    #[serde(default)]
+    pub visible: Option<VisibleSquares>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub fog: Option<Color>,
+    #[serde(default)]
     pub orientation: Orientation,
     #[serde(default)]
     pub theme: BoardTheme,
@@ -201,12 +772,21 @@ pub struct RequestParams {
     pub piece: PieceSet,
     #[serde(default)]
     pub coordinates: Coordinates,
+    #[serde(default)]
+    pub blindfold: Blindfold,
+    #[serde(default)]
+    pub material: bool,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub region: Region,
 }
 
+#[serde_as]
 #[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RequestBody {
-    pub white: Option<PlayerName>,
-    pub black: Option<PlayerName>,
+    pub white: Option<Player>,
+    pub black: Option<Player>,
     pub comment: Option<Comment>,
     pub frames: Vec<RequestFrame>,
     #[serde(default)]
@@ -219,10 +799,150 @@ pub struct RequestBody {
     pub piece: PieceSet,
     #[serde(default)]
     pub coordinates: Coordinates,
+    #[serde(default)]
+    pub blindfold: Blindfold,
+    #[serde(default)]
+    pub caption: bool,
+    #[serde(default)]
+    pub material: bool,
+    pub result: Option<GameResult>,
+    #[serde(default)]
+    pub trailer: Trailer,
+    #[serde(default)]
+    pub plays: u16,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub region: Region,
+}
+
+#[serde_as]
+#[derive(Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GridBoard {
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub fen: Fen,
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, rename = "lastMove")]
+    pub last_move: Option<UciMove>,
+    #[serde(default)]
+    pub check: CheckSquare,
+    #[serde(default)]
+    pub highlights: Highlights,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub selected: Option<Square>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub premove: Option<UciMove>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub ghost: Option<Ghost>,
+    #[serde(default)]
+    pub visible: Option<VisibleSquares>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub fog: Option<Color>,
+    pub caption: Option<BannerText>,
+}
+
+impl GridBoard {
+    pub fn frame(&self) -> RequestFrame {
+        RequestFrame {
+            fen: self.fen.clone(),
+            last_move: self.last_move,
+            check: self.check,
+            highlights: self.highlights,
+            selected: self.selected,
+            premove: self.premove,
+            ghost: self.ghost,
+            visible: self.visible,
+            fog: self.fog,
+            ..RequestFrame::default()
+        }
+    }
+}
+
+pub type GridBoards = ArrayVec<GridBoard, 64>; // length limited to prevent dos
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GridRequest {
+    pub comment: Option<Comment>,
+    pub boards: GridBoards,
+    pub columns: Option<u8>,
+    pub spacing: Option<u8>,
+    #[serde(default)]
+    pub theme: BoardTheme,
+    #[serde(default)]
+    pub piece: PieceSet,
+    #[serde(default)]
+    pub coordinates: Coordinates,
+    #[serde(default)]
+    pub blindfold: Blindfold,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GridGame {
+    #[serde(default)]
+    pub orientation: Orientation,
+    pub caption: Option<BannerText>,
+    pub frames: Vec<RequestFrame>,
+}
+
+pub type GridGames = ArrayVec<GridGame, 64>; // length limited to prevent dos
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GridAnimationRequest {
+    pub comment: Option<Comment>,
+    pub games: GridGames,
+    pub columns: Option<u8>,
+    pub spacing: Option<u8>,
+    #[serde(default)]
+    pub delay: u16,
+    #[serde(default)]
+    pub theme: BoardTheme,
+    #[serde(default)]
+    pub piece: PieceSet,
+    #[serde(default)]
+    pub coordinates: Coordinates,
+    #[serde(default)]
+    pub blindfold: Blindfold,
+    #[serde(default)]
+    pub trailer: Trailer,
+    #[serde(default)]
+    pub plays: u16,
+}
+
+#[serde_as]
+#[derive(Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GalleryParams {
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub fen: Fen,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, rename = "lastMove")]
+    pub last_move: Option<UciMove>,
+    #[serde(default)]
+    pub orientation: Orientation,
+    /// Show every board theme with this piece set.
+    pub piece: Option<PieceSet>,
+    /// Show every piece set on this board theme, unless `piece` is given.
+    pub theme: Option<BoardTheme>,
+    #[serde(default)]
+    pub coordinates: Coordinates,
+    pub columns: Option<u8>,
+    pub spacing: Option<u8>,
 }
 
 #[serde_as]
 #[derive(Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RequestFrame {
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default)]
@@ -234,11 +954,80 @@ pub struct RequestFrame {
     pub last_move: Option<UciMove>,
     #[serde(default)]
     pub check: CheckSquare,
+    #[serde(default)]
+    pub highlights: Highlights,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub selected: Option<Square>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub premove: Option<UciMove>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub ghost: Option<Ghost>,
+    #[serde(default)]
+    pub visible: Option<VisibleSquares>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub fog: Option<Color>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     #[serde(default)]
     pub glyph: Option<MoveGlyph>,
     #[serde(default)]
     pub clock: FrameClock,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub san: Option<SanPlus>,
+}
+
+impl RequestParams {
+    /// Flat player fields of the query string, with the nested names that
+    /// replace them in the versioned API.
+    pub const PLAYER_FIELDS: [(&'static str, &'static str); 8] = [
+        ("whiteTitle", "white.title"),
+        ("whiteRating", "white.rating"),
+        ("whiteRatingDiff", "white.ratingDiff"),
+        ("whiteTag", "white.tag"),
+        ("blackTitle", "black.title"),
+        ("blackRating", "black.rating"),
+        ("blackRatingDiff", "black.ratingDiff"),
+        ("blackTag", "black.tag"),
+    ];
+
+    pub fn frame(&self) -> RequestFrame {
+        RequestFrame {
+            fen: self.fen.clone(),
+            last_move: self.last_move,
+            check: self.check,
+            highlights: self.highlights,
+            selected: self.selected,
+            premove: self.premove,
+            ghost: self.ghost,
+            visible: self.visible,
+            fog: self.fog,
+            ..RequestFrame::default()
+        }
+    }
+
+    pub fn white_player(&mut self) -> Option<Player> {
+        Player::with_details(
+            self.white.take(),
+            self.white_title,
+            self.white_rating,
+            self.white_rating_diff,
+            self.white_tag,
+        )
+    }
+
+    pub fn black_player(&mut self) -> Option<Player> {
+        Player::with_details(
+            self.black.take(),
+            self.black_title,
+            self.black_rating,
+            self.black_rating_diff,
+            self.black_tag,
+        )
+    }
 }
 
 impl RequestBody {
@@ -275,6 +1064,12 @@ impl RequestBody {
                     CheckSquare::No
                 },
                 last_move: Some(UciMove::from_move(m, CastlingMode::Standard)),
+                highlights: Highlights::default(),
+                selected: None,
+                premove: None,
+                ghost: None,
+                visible: None,
+                fog: None,
                 delay: None,
                 glyph: match &pgn_move.as_bytes()[prefix..] {
                     b"?!" => Some(MoveGlyph::Dubious),
@@ -283,6 +1078,7 @@ impl RequestBody {
                     _ => None,
                 },
                 clock: FrameClock::default(),
+                san: None,
             })
         }
 
@@ -290,14 +1086,31 @@ impl RequestBody {
 
         RequestBody {
             comment: Some(Comment::from("https://lichess.org/Q0iQs5Zi").unwrap()),
-            white: Some(PlayerName::from("GM DrDrunkenstein (2888)").unwrap()),
-            black: Some(PlayerName::from("GM Zhigalko_Sergei (2895)").unwrap()),
+            white: Some(Player {
+                name: PlayerName::from("DrDrunkenstein").unwrap(),
+                title: Some(Title::Gm),
+                rating: Some(2888),
+                ..Player::default()
+            }),
+            black: Some(Player {
+                name: PlayerName::from("Zhigalko_Sergei").unwrap(),
+                title: Some(Title::Gm),
+                rating: Some(2895),
+                ..Player::default()
+            }),
             orientation: Orientation::White,
             delay: 50,
             frames,
             theme: BoardTheme::default(),
             piece: PieceSet::default(),
             coordinates: Coordinates::default(),
+            blindfold: Blindfold::default(),
+            caption: false,
+            material: false,
+            result: None,
+            trailer: Trailer::default(),
+            plays: 0,
+            region: Region::default(),
         }
     }
 }
@@ -1,36 +1,79 @@
-use serde::Deserialize;
+use std::str::FromStr;
 
-#[derive(Deserialize, Debug, Default, Copy, Clone)]
-#[serde(rename_all = "lowercase")]
+use serde::{Deserialize, Deserializer, de};
+use strum::{EnumMessage, VariantNames};
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    strum::EnumString,
+    strum::IntoStaticStr,
+    strum::EnumMessage,
+    strum::VariantNames,
+)]
+#[strum(serialize_all = "lowercase")]
 pub enum BoardTheme {
-    #[serde(alias = "blue2")]
-    #[serde(alias = "blue3")]
-    #[serde(alias = "blue-marble")]
-    #[serde(alias = "canvas")]
+    #[strum(
+        to_string = "blue",
+        serialize = "blue2",
+        serialize = "blue3",
+        serialize = "blue-marble",
+        serialize = "canvas"
+    )]
     Blue,
-    #[serde(alias = "wood")]
-    #[serde(alias = "wood2")]
-    #[serde(alias = "wood3")]
-    #[serde(alias = "wood4")]
-    #[serde(alias = "maple")]
-    #[serde(alias = "maple2")]
-    #[serde(alias = "marble")]
-    #[serde(alias = "grey")]
-    #[serde(alias = "metal")]
-    #[serde(alias = "olive")]
-    #[serde(alias = "newspaper")]
-    #[serde(alias = "horsey")]
+    #[strum(
+        to_string = "brown",
+        serialize = "wood",
+        serialize = "wood2",
+        serialize = "wood3",
+        serialize = "wood4",
+        serialize = "maple",
+        serialize = "maple2",
+        serialize = "marble",
+        serialize = "grey",
+        serialize = "metal",
+        serialize = "olive",
+        serialize = "newspaper",
+        serialize = "horsey"
+    )]
     #[default]
     Brown,
-    #[serde(alias = "green-plastic")]
+    #[strum(to_string = "green", serialize = "green-plastic")]
     Green,
-    #[serde(alias = "leather")]
+    #[strum(to_string = "ic", serialize = "leather")]
     Ic,
     Pink,
-    #[serde(alias = "purple-diag")]
+    #[strum(to_string = "purple", serialize = "purple-diag")]
     Purple,
 }
 
+impl BoardTheme {
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    /// Other accepted names, mostly for boards of lichess.org that are not
+    /// available as sprites.
+    pub fn aliases(self) -> impl Iterator<Item = &'static str> {
+        let name = self.name();
+        self.get_serializations()
+            .iter()
+            .copied()
+            .filter(move |&s| s != name)
+    }
+
+    pub const ALL: [BoardTheme; 6] = [
+        BoardTheme::Blue,
+        BoardTheme::Brown,
+        BoardTheme::Green,
+        BoardTheme::Ic,
+        BoardTheme::Pink,
+        BoardTheme::Purple,
+    ];
+}
+
 pub struct ByBoardTheme<T> {
     inner: [T; 6],
 }
@@ -40,9 +83,8 @@ impl<T> ByBoardTheme<T> {
     where
         F: FnMut(BoardTheme) -> T,
     {
-        use BoardTheme::*;
         ByBoardTheme {
-            inner: [Blue, Brown, Green, Ic, Pink, Purple].map(f),
+            inner: BoardTheme::ALL.map(f),
         }
     }
 
@@ -51,8 +93,19 @@ impl<T> ByBoardTheme<T> {
     }
 }
 
-#[derive(Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq)]
-#[serde(rename_all = "lowercase")]
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    strum::EnumString,
+    strum::IntoStaticStr,
+    strum::EnumMessage,
+    strum::VariantNames,
+)]
+#[strum(serialize_all = "lowercase")]
 pub enum PieceSet {
     Alpha,
     Anarcandy,
@@ -75,7 +128,7 @@ pub enum PieceSet {
     Governor,
     Horsey,
     Icpieces,
-    #[serde(rename = "kiwen-suwi")]
+    #[strum(to_string = "kiwen-suwi", serialize = "kiwensuwi")]
     KiwenSuwi,
     Kosal,
     Leipzig,
@@ -96,6 +149,62 @@ pub enum PieceSet {
     Xkcd,
 }
 
+impl PieceSet {
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    /// Other accepted names.
+    pub fn aliases(self) -> impl Iterator<Item = &'static str> {
+        let name = self.name();
+        self.get_serializations()
+            .iter()
+            .copied()
+            .filter(move |&s| s != name)
+    }
+
+    pub const ALL: [PieceSet; 38] = [
+        PieceSet::Alpha,
+        PieceSet::Anarcandy,
+        PieceSet::Caliente,
+        PieceSet::California,
+        PieceSet::Cardinal,
+        PieceSet::Cburnett,
+        PieceSet::Celtic,
+        PieceSet::Chess7,
+        PieceSet::Chessnut,
+        PieceSet::Companion,
+        PieceSet::Cooke,
+        PieceSet::Disguised,
+        PieceSet::Dubrovny,
+        PieceSet::Fantasy,
+        PieceSet::Firi,
+        PieceSet::Fresca,
+        PieceSet::Gioco,
+        PieceSet::Governor,
+        PieceSet::Horsey,
+        PieceSet::Icpieces,
+        PieceSet::KiwenSuwi,
+        PieceSet::Kosal,
+        PieceSet::Leipzig,
+        PieceSet::Letter,
+        PieceSet::Maestro,
+        PieceSet::Merida,
+        PieceSet::Monarchy,
+        PieceSet::Mpchess,
+        PieceSet::Pirouetti,
+        PieceSet::Pixel,
+        PieceSet::Reillycraig,
+        PieceSet::Rhosgfx,
+        PieceSet::Riohacha,
+        PieceSet::Shapes,
+        PieceSet::Spatial,
+        PieceSet::Staunty,
+        PieceSet::Tatiana,
+        PieceSet::Xkcd,
+    ];
+}
+
 pub struct ByPieceSet<T> {
     inner: [T; 38],
 }
@@ -105,49 +214,8 @@ impl<T> ByPieceSet<T> {
     where
         F: FnMut(PieceSet) -> T,
     {
-        use PieceSet::*;
         ByPieceSet {
-            inner: [
-                Alpha,
-                Anarcandy,
-                Caliente,
-                California,
-                Cardinal,
-                Cburnett,
-                Celtic,
-                Chess7,
-                Chessnut,
-                Companion,
-                Cooke,
-                Disguised,
-                Dubrovny,
-                Fantasy,
-                Firi,
-                Fresca,
-                Gioco,
-                Governor,
-                Horsey,
-                Icpieces,
-                KiwenSuwi,
-                Kosal,
-                Leipzig,
-                Letter,
-                Maestro,
-                Merida,
-                Monarchy,
-                Mpchess,
-                Pirouetti,
-                Pixel,
-                Reillycraig,
-                Rhosgfx,
-                Riohacha,
-                Shapes,
-                Spatial,
-                Staunty,
-                Tatiana,
-                Xkcd,
-            ]
-            .map(f),
+            inner: PieceSet::ALL.map(f),
         }
     }
 
@@ -156,6 +224,37 @@ impl<T> ByPieceSet<T> {
     }
 }
 
+fn deserialize_name<'de, D, T>(
+    deserializer: D,
+    expected: &'static [&'static str],
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+{
+    let name = String::deserialize(deserializer)?;
+    name.parse()
+        .map_err(|_| de::Error::unknown_variant(&name, expected))
+}
+
+impl<'de> Deserialize<'de> for BoardTheme {
+    fn deserialize<D>(deserializer: D) -> Result<BoardTheme, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_name(deserializer, BoardTheme::VARIANTS)
+    }
+}
+
+impl<'de> Deserialize<'de> for PieceSet {
+    fn deserialize<D>(deserializer: D) -> Result<PieceSet, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_name(deserializer, PieceSet::VARIANTS)
+    }
+}
+
 pub fn sprite_data(board: BoardTheme, pieces: PieceSet) -> &'static [u8] {
     use PieceSet::*;
     match board {
@@ -420,4 +519,32 @@ mod tests {
             &PieceSet::Tatiana
         );
     }
+
+    #[test]
+    fn test_names() {
+        for board in BoardTheme::ALL {
+            assert_eq!(
+                board.name().parse::<BoardTheme>().map(|b| b as usize),
+                Ok(board as usize)
+            );
+            for alias in board.aliases() {
+                assert_eq!(
+                    alias.parse::<BoardTheme>().map(|b| b as usize),
+                    Ok(board as usize)
+                );
+            }
+        }
+        for piece in PieceSet::ALL {
+            assert_eq!(piece.name().parse(), Ok(piece));
+            for alias in piece.aliases() {
+                assert_eq!(alias.parse(), Ok(piece));
+            }
+        }
+        assert_eq!(PieceSet::KiwenSuwi.name(), "kiwen-suwi");
+        assert_eq!(
+            PieceSet::KiwenSuwi.aliases().collect::<Vec<_>>(),
+            ["kiwensuwi"]
+        );
+        assert_eq!(BoardTheme::VARIANTS.len(), BoardTheme::ALL.len());
+    }
 }
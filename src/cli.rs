@@ -0,0 +1,353 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::{Args, Subcommand};
+use lila_gif::{
+    Renderer, Themes,
+    api::{
+        CheckSquare, Coordinates, GridAnimationRequest, GridRequest, Orientation, RequestBody,
+        RequestParams,
+    },
+    assets::{BoardTheme, PieceSet},
+};
+use serde::{
+    Deserialize,
+    de::{DeserializeOwned, IntoDeserializer, value},
+};
+use shakmaty::{fen::Fen, uci::UciMove};
+
+#[derive(Args)]
+pub struct RenderOpt {
+    /// Write the GIF to this file instead of stdout.
+    #[arg(short = 'o', long = "output", global = true)]
+    output: Option<PathBuf>,
+    #[command(subcommand)]
+    command: RenderCommand,
+}
+
+#[derive(Subcommand)]
+enum RenderCommand {
+    /// Render a single position given as FEN.
+    Fen(FenOpt),
+    /// Render a single position from a JSON /image.gif request.
+    Image(InputOpt),
+    /// Render an animated game from a JSON /game.gif request.
+    Game(InputOpt),
+    /// Render a grid of positions from a JSON /grid.gif request.
+    Grid(InputOpt),
+    /// Render a grid of animated games from a JSON /games.gif request.
+    Games(InputOpt),
+}
+
+#[derive(Args)]
+struct FenOpt {
+    /// Position to render.
+    fen: Fen,
+    /// Highlight the last move, in UCI notation.
+    #[arg(long = "last-move")]
+    last_move: Option<UciMove>,
+    /// Highlight the king in check.
+    #[arg(long = "check")]
+    check: bool,
+    #[arg(long = "orientation", value_parser = parse_serde::<Orientation>)]
+    orientation: Option<Orientation>,
+    #[arg(long = "theme", value_parser = parse_serde::<BoardTheme>)]
+    theme: Option<BoardTheme>,
+    #[arg(long = "piece", value_parser = parse_serde::<PieceSet>)]
+    piece: Option<PieceSet>,
+    #[arg(long = "coordinates", value_parser = parse_serde::<Coordinates>)]
+    coordinates: Option<Coordinates>,
+}
+
+#[derive(Args)]
+struct InputOpt {
+    /// Read the JSON request from this file, or from stdin if omitted or `-`.
+    input: Option<PathBuf>,
+}
+
+impl InputOpt {
+    fn read_to_string(&self) -> io::Result<String> {
+        let mut buf = String::new();
+        match self.input {
+            Some(ref path) if path.as_os_str() != "-" => {
+                File::open(path)?.read_to_string(&mut buf)?;
+            }
+            _ => {
+                io::stdin().lock().read_to_string(&mut buf)?;
+            }
+        }
+        Ok(buf)
+    }
+
+    fn read<T: DeserializeOwned>(&self) -> io::Result<T> {
+        serde_json::from_str(&self.read_to_string()?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+fn parse_serde<T: DeserializeOwned>(s: &str) -> Result<T, value::Error> {
+    T::deserialize(s.into_deserializer())
+}
+
+pub fn render(themes: &Themes, opt: RenderOpt) -> io::Result<()> {
+    let renderer = Renderer::new(themes);
+    let renderer = match opt.command {
+        RenderCommand::Fen(fen) => {
+            let mut renderer = renderer.image(RequestParams::default()).position(fen.fen);
+            if let Some(last_move) = fen.last_move {
+                renderer = renderer.last_move(last_move);
+            }
+            if fen.check {
+                renderer = renderer.check(CheckSquare::Yes);
+            }
+            if let Some(orientation) = fen.orientation {
+                renderer = renderer.orientation(orientation);
+            }
+            if let Some(theme) = fen.theme {
+                renderer = renderer.theme(theme);
+            }
+            if let Some(piece) = fen.piece {
+                renderer = renderer.piece(piece);
+            }
+            if let Some(coordinates) = fen.coordinates {
+                renderer = renderer.coordinates(coordinates);
+            }
+            renderer
+        }
+        RenderCommand::Image(input) => renderer.image(input.read::<RequestParams>()?),
+        RenderCommand::Game(input) => renderer.game(input.read::<RequestBody>()?),
+        RenderCommand::Grid(input) => renderer.grid(input.read::<GridRequest>()?),
+        RenderCommand::Games(input) => renderer.games(input.read::<GridAnimationRequest>()?),
+    };
+
+    match opt.output {
+        Some(path) => {
+            let mut output = BufWriter::new(File::create(path)?);
+            renderer.write_to(&mut output)?;
+            output.flush()
+        }
+        None => {
+            let mut output = io::stdout().lock();
+            renderer.write_to(&mut output)?;
+            output.flush()
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct BatchOpt {
+    /// Read JSON requests, one per line, from this file, or from stdin if
+    /// omitted or `-`. Lines with `frames` are rendered like /game.gif,
+    /// others like /image.gif.
+    input: Option<PathBuf>,
+    /// Write `<id>.gif` files into this directory.
+    #[arg(short = 'o', long = "out-dir", default_value = ".")]
+    out_dir: PathBuf,
+    /// Render this many requests in parallel. Defaults to the number of
+    /// cores.
+    #[arg(short = 'j', long = "jobs")]
+    jobs: Option<NonZeroUsize>,
+}
+
+struct BatchRequest {
+    line: usize,
+    id: String,
+    value: Result<serde_json::Value, String>,
+}
+
+struct Outcome {
+    line: usize,
+    id: String,
+    result: Result<usize, String>,
+    elapsed: Duration,
+}
+
+fn batch_id(line: usize, value: &serde_json::Value) -> Result<String, String> {
+    let id = match value.get("id") {
+        None => return Ok(format!("line-{line}")),
+        Some(serde_json::Value::String(id)) => id.clone(),
+        Some(serde_json::Value::Number(id)) => id.to_string(),
+        Some(_) => return Err("id must be a string or number".to_owned()),
+    };
+    if id.is_empty()
+        || id.starts_with('.')
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!("id {id:?} is not a valid file name"));
+    }
+    Ok(id)
+}
+
+/// Parses all lines up front, so that repeated ids are reported instead of
+/// overwriting each other's output. Only the first use of an id is rendered.
+fn batch_requests(lines: &[(usize, &str)]) -> Vec<BatchRequest> {
+    let mut seen = HashMap::new();
+    lines
+        .iter()
+        .map(|&(line, json)| {
+            let (id, value) = match serde_json::from_str::<serde_json::Value>(json) {
+                Ok(value) => match batch_id(line, &value) {
+                    Ok(id) => match seen.entry(id.clone()) {
+                        Entry::Occupied(first) => (
+                            id,
+                            Err(format!("duplicate id, first used on line {}", first.get())),
+                        ),
+                        Entry::Vacant(entry) => {
+                            entry.insert(line);
+                            (id, Ok(value))
+                        }
+                    },
+                    Err(err) => (format!("line-{line}"), Err(err)),
+                },
+                Err(err) => (format!("line-{line}"), Err(err.to_string())),
+            };
+            BatchRequest { line, id, value }
+        })
+        .collect()
+}
+
+fn batch_render(
+    themes: &Themes,
+    out_dir: &Path,
+    value: &serde_json::Value,
+    id: &str,
+) -> Result<usize, String> {
+    let renderer = Renderer::new(themes);
+    let renderer = if value.get("frames").is_some() {
+        renderer.game(RequestBody::deserialize(value).map_err(|err| err.to_string())?)
+    } else {
+        renderer.image(RequestParams::deserialize(value).map_err(|err| err.to_string())?)
+    };
+    let gif = panic::catch_unwind(AssertUnwindSafe(|| renderer.to_vec()))
+        .map_err(|_| "render panicked".to_owned())?
+        .map_err(|err| err.to_string())?;
+    fs::write(out_dir.join(format!("{id}.gif")), &gif).map_err(|err| err.to_string())?;
+    Ok(gif.len())
+}
+
+pub fn batch(themes: &Themes, opt: BatchOpt) -> io::Result<bool> {
+    let input = InputOpt { input: opt.input }.read_to_string()?;
+    let lines: Vec<(usize, &str)> = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    let requests = batch_requests(&lines);
+    fs::create_dir_all(&opt.out_dir)?;
+
+    let jobs = opt
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    let next = AtomicUsize::new(0);
+    let started = Instant::now();
+
+    let mut outcomes: Vec<Outcome> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outcomes = Vec::new();
+                    while let Some(request) = requests.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let started = Instant::now();
+                        let result = match request.value {
+                            Ok(ref value) => batch_render(themes, &opt.out_dir, value, &request.id),
+                            Err(ref err) => Err(err.clone()),
+                        };
+                        outcomes.push(Outcome {
+                            line: request.line,
+                            id: request.id.clone(),
+                            result,
+                            elapsed: started.elapsed(),
+                        });
+                    }
+                    outcomes
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("batch worker"))
+            .collect()
+    });
+    outcomes.sort_by_key(|outcome| outcome.line);
+
+    let elapsed = started.elapsed();
+    let mut bytes = 0;
+    let mut failures = 0;
+    for outcome in &outcomes {
+        match outcome.result {
+            Ok(len) => bytes += len,
+            Err(ref err) => {
+                failures += 1;
+                eprintln!("line {} ({}): {}", outcome.line, outcome.id, err);
+            }
+        }
+    }
+    let rendered: Vec<Duration> = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_ok())
+        .map(|outcome| outcome.elapsed)
+        .collect();
+    eprintln!(
+        "rendered {} of {} in {:.2?} (jobs: {}), {} failed, {} bytes",
+        rendered.len(),
+        outcomes.len(),
+        elapsed,
+        jobs,
+        failures,
+        bytes
+    );
+    if let Some(max) = rendered.iter().max() {
+        eprintln!(
+            "per request: mean {:.2?}, max {:.2?}",
+            rendered.iter().sum::<Duration>() / rendered.len() as u32,
+            max
+        );
+    }
+    Ok(failures == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_ids() {
+        let requests = batch_requests(&[
+            (1, r#"{"id": "a"}"#),
+            (2, r#"{"id": "b"}"#),
+            (3, r#"{"id": "a"}"#),
+            (4, r#"{"id": "line-5"}"#),
+            (5, r#"{}"#),
+        ]);
+        let errors: Vec<_> = requests
+            .iter()
+            .map(|request| (request.id.as_str(), request.value.as_ref().err().cloned()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("a", None),
+                ("b", None),
+                ("a", Some("duplicate id, first used on line 1".to_owned())),
+                ("line-5", None),
+                (
+                    "line-5",
+                    Some("duplicate id, first used on line 4".to_owned())
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,614 @@
+use std::{error::Error, fmt, iter::FusedIterator, ptr, vec};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use gift::{Encoder, block};
+use ndarray::{ArrayView2, ArrayViewMut2, s};
+use rusttype::Font;
+
+use crate::{
+    api::{
+        BannerText, Comment, Coordinates, GalleryParams, GridAnimationRequest, GridRequest,
+        RequestFrame, Trailer,
+    },
+    assets::{BoardTheme, PieceSet},
+    quantize::{self, Histogram},
+    render::{
+        BoardView, RenderFrame, encode_trailer, loop_count, render_board, render_caption_region,
+        render_diff,
+    },
+    theme::{Theme, Themes},
+};
+
+const DEFAULT_SPACING: usize = 30;
+pub(crate) const MAX_SPACING: usize = 200;
+/// Enough for 64 boards in a square grid with outside coordinates and
+/// captions, while bounding the canvas allocated per request.
+pub(crate) const MAX_PIXELS: usize = 50_000_000;
+
+/// The requested grid does not fit into a GIF, or exceeds [`MAX_PIXELS`].
+#[derive(Debug)]
+pub struct GridTooLarge {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl fmt::Display for GridTooLarge {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "grid of {}x{} pixels is too large, expected at most {} pixels per side and {} in total",
+            self.width,
+            self.height,
+            u16::MAX,
+            MAX_PIXELS
+        )
+    }
+}
+
+impl Error for GridTooLarge {}
+
+/// Converts a checked coordinate or size within the grid.
+fn px(value: usize) -> u16 {
+    u16::try_from(value).expect("grid size checked against gif limits")
+}
+
+enum GridState {
+    Preamble,
+    Frames,
+    Complete,
+}
+
+struct GridLayout {
+    columns: usize,
+    rows: usize,
+    spacing: usize,
+    board_size: usize,
+    cell_width: usize,
+    cell_height: usize,
+}
+
+impl GridLayout {
+    fn new(
+        theme: &Theme,
+        count: usize,
+        columns: Option<u8>,
+        spacing: Option<u8>,
+        coordinates: Coordinates,
+        captions: bool,
+    ) -> GridLayout {
+        let board = BoardView {
+            coordinates,
+            ..BoardView::default()
+        };
+        let board_size = theme.width() + 2 * board.border(theme);
+        let count = count.max(1);
+        let columns = match columns {
+            Some(columns) => usize::from(columns).clamp(1, count),
+            None => count.isqrt() + usize::from(count.isqrt().pow(2) < count),
+        };
+        GridLayout {
+            columns,
+            rows: count.div_ceil(columns),
+            spacing: spacing
+                .map_or(DEFAULT_SPACING, usize::from)
+                .min(MAX_SPACING),
+            board_size,
+            cell_width: board_size,
+            cell_height: board_size + if captions { theme.bar_height() } else { 0 },
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.columns * (self.cell_width + self.spacing) + self.spacing
+    }
+
+    fn height(&self) -> usize {
+        self.rows * (self.cell_height + self.spacing) + self.spacing
+    }
+
+    fn check(&self) -> Result<(), GridTooLarge> {
+        let (width, height) = (self.width(), self.height());
+        if width > usize::from(u16::MAX)
+            || height > usize::from(u16::MAX)
+            || width * height > MAX_PIXELS
+        {
+            Err(GridTooLarge { width, height })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn cell<'a>(
+        &self,
+        idx: usize,
+        theme: &'a Theme,
+        board: BoardView,
+        caption: Option<BannerText>,
+        frames: Vec<RenderFrame>,
+    ) -> GridCell<'a> {
+        GridCell {
+            theme,
+            left: self.spacing + (idx % self.columns) * (self.cell_width + self.spacing),
+            top: self.spacing + (idx / self.columns) * (self.cell_height + self.spacing),
+            board,
+            caption: caption.map(|c| c.to_string()),
+            frames: frames.into_iter(),
+            prev: RenderFrame::default(),
+        }
+    }
+}
+
+/// A board in the grid. Cells with a theme other than the one of the grid
+/// are encoded as separate images with a local color table.
+struct GridCell<'a> {
+    theme: &'a Theme,
+    left: usize,
+    top: usize,
+    board: BoardView,
+    caption: Option<String>,
+    frames: vec::IntoIter<RenderFrame>,
+    prev: RenderFrame,
+}
+
+pub struct GridRender<'a> {
+    theme: &'a Theme,
+    font: &'a Font<'a>,
+    state: GridState,
+    comment: Option<Comment>,
+    width: usize,
+    height: usize,
+    board_size: usize,
+    cells: Vec<GridCell<'a>>,
+    buffer: Vec<u8>,
+    trailer: Trailer,
+    loop_count: Option<u16>,
+}
+
+impl<'a> GridRender<'a> {
+    pub fn new_image(
+        themes: &'a Themes,
+        params: GridRequest,
+    ) -> Result<GridRender<'a>, GridTooLarge> {
+        let theme = themes.get(params.theme, params.piece);
+        let layout = GridLayout::new(
+            theme,
+            params.boards.len(),
+            params.columns,
+            params.spacing,
+            params.coordinates,
+            params.boards.iter().any(|b| b.caption.is_some()),
+        );
+        layout.check()?;
+
+        let cells = params
+            .boards
+            .into_iter()
+            .enumerate()
+            .map(|(idx, board)| {
+                layout.cell(
+                    idx,
+                    theme,
+                    BoardView {
+                        orientation: board.orientation,
+                        coordinates: params.coordinates,
+                        blindfold: params.blindfold,
+                        ..BoardView::default()
+                    },
+                    board.caption,
+                    vec![RenderFrame::new(&board.frame())],
+                )
+            })
+            .collect();
+
+        Ok(GridRender {
+            theme,
+            font: themes.font(),
+            state: GridState::Preamble,
+            comment: params.comment,
+            width: layout.width(),
+            height: layout.height(),
+            board_size: layout.board_size,
+            cells,
+            buffer: vec![0; theme.width() * theme.width()],
+            trailer: Trailer::None,
+            loop_count: None,
+        })
+    }
+
+    pub fn new_animation(
+        themes: &'a Themes,
+        params: GridAnimationRequest,
+    ) -> Result<GridRender<'a>, GridTooLarge> {
+        let theme = themes.get(params.theme, params.piece);
+        let layout = GridLayout::new(
+            theme,
+            params.games.len(),
+            params.columns,
+            params.spacing,
+            params.coordinates,
+            params.games.iter().any(|g| g.caption.is_some()),
+        );
+        layout.check()?;
+
+        let cells = params
+            .games
+            .into_iter()
+            .enumerate()
+            .map(|(idx, game)| {
+                layout.cell(
+                    idx,
+                    theme,
+                    BoardView {
+                        orientation: game.orientation,
+                        coordinates: params.coordinates,
+                        blindfold: params.blindfold,
+                        ..BoardView::default()
+                    },
+                    game.caption,
+                    RenderFrame::animation(game.frames, params.delay, false),
+                )
+            })
+            .collect();
+
+        Ok(GridRender {
+            theme,
+            font: themes.font(),
+            state: GridState::Preamble,
+            comment: params.comment,
+            width: layout.width(),
+            height: layout.height(),
+            board_size: layout.board_size,
+            cells,
+            buffer: vec![0; theme.width() * theme.width()],
+            trailer: params.trailer,
+            loop_count: loop_count(params.plays),
+        })
+    }
+
+    /// Contact sheet of one position in every piece set on a board theme, or
+    /// in every board theme with a piece set, captioned with their names.
+    pub fn new_gallery(
+        themes: &'a Themes,
+        params: GalleryParams,
+    ) -> Result<GridRender<'a>, GridTooLarge> {
+        let entries: Vec<(&Theme, &str)> = match params.piece {
+            Some(piece) => BoardTheme::ALL
+                .into_iter()
+                .map(|board| (themes.get(board, piece), board.name()))
+                .collect(),
+            None => {
+                let board = params.theme.unwrap_or_default();
+                PieceSet::ALL
+                    .into_iter()
+                    .map(|piece| (themes.get(board, piece), piece.name()))
+                    .collect()
+            }
+        };
+
+        let theme = entries[0].0;
+        let layout = GridLayout::new(
+            theme,
+            entries.len(),
+            params.columns,
+            params.spacing,
+            params.coordinates,
+            true,
+        );
+        layout.check()?;
+
+        let frame = RequestFrame {
+            fen: params.fen,
+            last_move: params.last_move,
+            ..RequestFrame::default()
+        };
+        let cells = entries
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (cell_theme, name))| {
+                layout.cell(
+                    idx,
+                    cell_theme,
+                    BoardView {
+                        orientation: params.orientation,
+                        coordinates: params.coordinates,
+                        ..BoardView::default()
+                    },
+                    BannerText::from(name).ok(),
+                    vec![RenderFrame::new(&frame)],
+                )
+            })
+            .collect();
+
+        Ok(GridRender {
+            theme,
+            font: themes.font(),
+            state: GridState::Preamble,
+            comment: None,
+            width: layout.width(),
+            height: layout.height(),
+            board_size: layout.board_size,
+            cells,
+            buffer: vec![0; theme.width() * theme.width()],
+            trailer: Trailer::None,
+            loop_count: None,
+        })
+    }
+}
+
+impl GridRender<'_> {
+    /// Renders the first frame of every cell onto one canvas.
+    ///
+    /// Cells drawn with a different theme than the grid (in galleries) are
+    /// quantized into a shared palette, returned as the global color table,
+    /// so that the whole grid is still a single image.
+    fn render_canvas(&mut self) -> (Vec<u8>, Option<block::GlobalColorTable>) {
+        let (board_size, bar_height) = (self.board_size, self.theme.bar_height());
+        let mut canvas = vec![self.theme.bar_color(); self.width * self.height];
+
+        if self.cells.iter().all(|c| ptr::eq(c.theme, self.theme)) {
+            let mut view =
+                ArrayViewMut2::from_shape((self.height, self.width), &mut canvas).expect("shape");
+            for cell in &mut self.cells {
+                let height = board_size + cell.caption.as_ref().map_or(0, |_| bar_height);
+                render_cell(
+                    view.slice_mut(s!(
+                        cell.top..(cell.top + height),
+                        cell.left..(cell.left + board_size)
+                    )),
+                    &mut self.buffer,
+                    self.font,
+                    cell,
+                );
+            }
+            return (canvas, None);
+        }
+
+        let mut histogram = Histogram::default();
+        let rendered: Vec<Vec<u8>> = self
+            .cells
+            .iter_mut()
+            .map(|cell| {
+                let height = board_size + cell.caption.as_ref().map_or(0, |_| bar_height);
+                let mut pixels = vec![cell.theme.bar_color(); board_size * height];
+                render_cell(
+                    ArrayViewMut2::from_shape((height, board_size), &mut pixels).expect("shape"),
+                    &mut self.buffer,
+                    self.font,
+                    cell,
+                );
+                histogram.add_indexed(cell.theme.global_color_table().colors(), &pixels);
+                pixels
+            })
+            .collect();
+
+        let colors = self.theme.global_color_table().colors();
+        let background = usize::from(self.theme.bar_color()) * 3;
+        let background = [
+            colors[background],
+            colors[background + 1],
+            colors[background + 2],
+        ];
+        let covered: usize = rendered.iter().map(Vec::len).sum();
+        histogram.add(background, (canvas.len() - covered) as u64);
+
+        // Index 255 overflows the code size computation in gift.
+        let palette = histogram.median_cut(255);
+        canvas.fill(quantize::nearest(&palette, background));
+        for (cell, pixels) in self.cells.iter().zip(rendered) {
+            let table = quantize::remap_table(cell.theme.global_color_table().colors(), &palette);
+            for (row, line) in pixels.chunks_exact(board_size).enumerate() {
+                let start = (cell.top + row) * self.width + cell.left;
+                for (dst, &src) in canvas[start..start + board_size].iter_mut().zip(line) {
+                    *dst = table[usize::from(src)];
+                }
+            }
+        }
+
+        let mut colors: Vec<u8> = palette.into_iter().flatten().collect();
+        colors.resize(256 * 3, 0);
+        (canvas, Some(block::GlobalColorTable::with_colors(&colors)))
+    }
+}
+
+impl Iterator for GridRender<'_> {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        let mut output = BytesMut::new().writer();
+        match self.state {
+            GridState::Preamble => {
+                let mut blocks = Encoder::new(&mut output).into_block_enc();
+
+                blocks.encode(block::Header::default()).expect("enc header");
+
+                let (canvas, shared_palette) = self.render_canvas();
+
+                blocks
+                    .encode(
+                        block::LogicalScreenDesc::default()
+                            .with_screen_height(px(self.height))
+                            .with_screen_width(px(self.width))
+                            .with_color_table_config(self.theme.color_table_config()),
+                    )
+                    .expect("enc logical screen desc");
+
+                blocks
+                    .encode(
+                        shared_palette.unwrap_or_else(|| self.theme.global_color_table().clone()),
+                    )
+                    .expect("enc global color table");
+
+                if let Some(loop_count) = self.loop_count {
+                    blocks
+                        .encode(block::Application::with_loop_count(loop_count))
+                        .expect("enc application");
+                }
+
+                let comment = self
+                    .comment
+                    .as_ref()
+                    .map_or("https://github.com/lichess-org/lila-gif".as_bytes(), |c| {
+                        c.as_bytes()
+                    });
+                if !comment.is_empty() {
+                    let mut comments = block::Comment::default();
+                    comments.add_comment(comment);
+                    blocks.encode(comments).expect("enc comment");
+                }
+
+                if let Some(delay) = self.cells.iter().filter_map(|c| c.prev.delay()).max() {
+                    let mut ctrl = block::GraphicControl::default();
+                    ctrl.set_delay_time_cs(delay);
+                    blocks.encode(ctrl).expect("enc graphic control");
+                }
+
+                blocks
+                    .encode(
+                        block::ImageDesc::default()
+                            .with_height(px(self.height))
+                            .with_width(px(self.width)),
+                    )
+                    .expect("enc image desc");
+
+                let mut image_data = block::ImageData::new(canvas.len());
+                image_data.data_mut().extend_from_slice(&canvas);
+                blocks.encode(image_data).expect("enc image data");
+
+                self.state = GridState::Frames;
+            }
+            GridState::Frames => {
+                let mut blocks = Encoder::new(&mut output).into_block_enc();
+
+                // Advance all boards in lockstep. Boards that have run out of
+                // frames keep showing their final position.
+                let step: Vec<_> = self
+                    .cells
+                    .iter_mut()
+                    .enumerate()
+                    .filter_map(|(idx, cell)| cell.frames.next().map(|frame| (idx, frame)))
+                    .collect();
+
+                if step.is_empty() {
+                    encode_trailer(
+                        &mut blocks,
+                        self.theme,
+                        self.trailer,
+                        self.height,
+                        self.width,
+                    );
+
+                    blocks
+                        .encode(block::Trailer::default())
+                        .expect("enc trailer");
+                    self.state = GridState::Complete;
+                } else {
+                    let delay = step.iter().filter_map(|(_, frame)| frame.delay()).max();
+
+                    // Changed squares of all boards, positioned on the grid.
+                    // Only animations have more than one frame, and all of
+                    // their boards share the grid theme.
+                    let mut parts = Vec::with_capacity(step.len());
+                    for (idx, frame) in step {
+                        let cell = &mut self.cells[idx];
+                        debug_assert!(ptr::eq(cell.theme, self.theme));
+                        let border = cell.board.border(cell.theme);
+                        let ((left, top), (w, h)) = render_diff(
+                            &mut self.buffer,
+                            cell.theme,
+                            cell.board,
+                            Some(&cell.prev),
+                            &frame,
+                            self.font,
+                        );
+                        parts.push((
+                            cell.left + border + left,
+                            cell.top + border + top,
+                            (h, w),
+                            self.buffer[..(w * h)].to_vec(),
+                        ));
+                        cell.prev = frame;
+                    }
+
+                    // Write the step as a single image, so that all boards
+                    // update at once, even in viewers that enforce a minimum
+                    // delay between images.
+                    let left = parts.iter().map(|&(l, _, _, _)| l).min().expect("step");
+                    let top = parts.iter().map(|&(_, t, _, _)| t).min().expect("step");
+                    let right = parts
+                        .iter()
+                        .map(|&(l, _, (_, w), _)| l + w)
+                        .max()
+                        .expect("step");
+                    let bottom = parts
+                        .iter()
+                        .map(|&(_, t, (h, _), _)| t + h)
+                        .max()
+                        .expect("step");
+                    let (width, height) = (right - left, bottom - top);
+
+                    let mut image = vec![self.theme.transparent_color(); width * height];
+                    let mut view =
+                        ArrayViewMut2::from_shape((height, width), &mut image).expect("shape");
+                    for (l, t, (h, w), data) in parts {
+                        view.slice_mut(s!((t - top)..(t - top + h), (l - left)..(l - left + w)))
+                            .assign(&ArrayView2::from_shape((h, w), &data).expect("part"));
+                    }
+
+                    let mut ctrl = block::GraphicControl::default();
+                    ctrl.set_disposal_method(block::DisposalMethod::Keep);
+                    ctrl.set_transparent_color(Some(self.theme.transparent_color()));
+                    if let Some(delay) = delay {
+                        ctrl.set_delay_time_cs(delay);
+                    }
+                    blocks.encode(ctrl).expect("enc graphic control");
+
+                    blocks
+                        .encode(
+                            block::ImageDesc::default()
+                                .with_left(px(left))
+                                .with_top(px(top))
+                                .with_height(px(height))
+                                .with_width(px(width)),
+                        )
+                        .expect("enc image desc");
+
+                    let mut image_data = block::ImageData::new(image.len());
+                    image_data.data_mut().extend_from_slice(&image);
+                    blocks.encode(image_data).expect("enc image data");
+                }
+            }
+            GridState::Complete => return None,
+        }
+        Some(output.into_inner().freeze())
+    }
+}
+
+impl FusedIterator for GridRender<'_> {}
+
+/// Renders the current frame of a cell and its caption into a view of
+/// the cell.
+fn render_cell(mut view: ArrayViewMut2<u8>, buffer: &mut [u8], font: &Font, cell: &mut GridCell) {
+    let theme = cell.theme;
+    let board_size = view.ncols();
+    cell.prev = cell.frames.next().unwrap_or_default();
+    render_board(
+        view.slice_mut(s!(..board_size, ..)),
+        buffer,
+        theme,
+        font,
+        cell.board,
+        &cell.prev,
+    );
+
+    if let Some(ref caption) = cell.caption {
+        let bar_height = theme.bar_height();
+        let region_width = render_caption_region(buffer, theme, font, caption, 0, board_size);
+        let src = ArrayView2::from_shape(
+            (bar_height, region_width),
+            &buffer[..bar_height * region_width],
+        )
+        .expect("caption src");
+        view.slice_mut(s!(board_size..(board_size + bar_height), ..region_width))
+            .assign(&src);
+    }
+}
@@ -0,0 +1,42 @@
+//! Render chess positions and games as GIF images, as used by
+//! [lichess.org](https://lichess.org).
+//!
+//! ```no_run
+//! use lila_gif::{Renderer, Themes};
+//!
+//! let themes = Themes::new();
+//! let mut output = Vec::new();
+//! Renderer::new(&themes)
+//!     .position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R".parse().unwrap())
+//!     .write_to(&mut output)
+//!     .unwrap();
+//! ```
+//!
+//! The `schema` feature adds JSON schemas of the request types, and `server`
+//! builds the HTTP server and command line tool on top of the library.
+
+pub mod api;
+pub mod assets;
+mod grid;
+mod quantize;
+mod render;
+mod renderer;
+#[cfg(feature = "schema")]
+pub mod schema;
+mod theme;
+pub mod validate;
+
+pub use grid::{GridRender, GridTooLarge};
+pub use render::Render;
+pub use renderer::{Chunks, Renderer};
+pub use theme::Themes;
+
+/// Rendering internals, exposed for the benchmarks only. Not part of the
+/// stable API.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::render::{
+        BoardView, RenderFrame, render_bar, render_caption_region, render_diff,
+    };
+}
@@ -1,33 +1,51 @@
-use std::{convert::Infallible, net::SocketAddr};
+mod cli;
+mod v2;
+
+use std::{convert::Infallible, io, net::SocketAddr, process};
 
 use axum::{
+    Json, Router,
     body::Body,
     extract::Query,
-    http::header::CONTENT_TYPE,
+    http::{StatusCode, header::CONTENT_TYPE},
     response::{IntoResponse, Response},
     routing::{get, post},
-    Json, Router,
 };
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use futures::stream;
+use lila_gif::{
+    GridRender, Render, Themes,
+    api::{GalleryParams, GridAnimationRequest, GridRequest, RequestBody, RequestParams},
+    schema,
+};
 use listenfd::ListenFd;
 use tikv_jemallocator::Jemalloc;
 use tokio::net::{TcpListener, UnixListener};
 
-mod api;
-mod assets;
-mod render;
-mod theme;
-
-use api::{RequestBody, RequestParams};
-use render::Render;
-use theme::Themes;
-
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Opt {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    serve: ServeOpt,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (default).
+    Serve(ServeOpt),
+    /// Render a GIF to a file or stdout.
+    Render(cli::RenderOpt),
+    /// Render many requests from a JSON lines file in parallel.
+    Batch(cli::BatchOpt),
+}
+
+#[derive(Args)]
+struct ServeOpt {
     /// Listen on this address.
     #[arg(long = "bind", env = "LILA_GIF_BIND", default_value = "127.0.0.1:6175")]
     bind: SocketAddr,
@@ -51,20 +69,93 @@ async fn game(themes: &'static Themes, Json(req): Json<RequestBody>) -> impl Int
         .unwrap()
 }
 
+async fn grid(themes: &'static Themes, Json(req): Json<GridRequest>) -> Response {
+    match GridRender::new_image(themes, req) {
+        Ok(render) => Response::builder()
+            .header(CONTENT_TYPE, "image/gif")
+            .body(Body::from_stream(stream::iter(
+                render.map(Ok::<_, Infallible>),
+            )))
+            .unwrap(),
+        Err(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+async fn games(themes: &'static Themes, Json(req): Json<GridAnimationRequest>) -> Response {
+    match GridRender::new_animation(themes, req) {
+        Ok(render) => Response::builder()
+            .header(CONTENT_TYPE, "image/gif")
+            .body(Body::from_stream(stream::iter(
+                render.map(Ok::<_, Infallible>),
+            )))
+            .unwrap(),
+        Err(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+async fn gallery(themes: &'static Themes, Query(req): Query<GalleryParams>) -> Response {
+    match GridRender::new_gallery(themes, req) {
+        Ok(render) => Response::builder()
+            .header(CONTENT_TYPE, "image/gif")
+            .body(Body::from_stream(stream::iter(
+                render.map(Ok::<_, Infallible>),
+            )))
+            .unwrap(),
+        Err(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
 async fn example(themes: &'static Themes) -> impl IntoResponse {
     game(themes, Json(RequestBody::example())).await
 }
 
-#[tokio::main]
-async fn main() {
+async fn openapi() -> impl IntoResponse {
+    Json(schema::openapi())
+}
+
+async fn capabilities() -> impl IntoResponse {
+    Json(schema::capabilities())
+}
+
+fn main() {
     let opt = Opt::parse();
 
     let themes: &'static Themes = Box::leak(Box::new(Themes::new()));
 
+    match opt.command.unwrap_or(Command::Serve(opt.serve)) {
+        Command::Serve(opt) => tokio::runtime::Runtime::new()
+            .expect("runtime")
+            .block_on(serve(themes, opt)),
+        Command::Render(opt) => {
+            if let Err(err) = cli::render(themes, opt)
+                && err.kind() != io::ErrorKind::BrokenPipe
+            {
+                eprintln!("lila-gif: {err}");
+                process::exit(1);
+            }
+        }
+        Command::Batch(opt) => match cli::batch(themes, opt) {
+            Ok(true) => (),
+            Ok(false) => process::exit(1),
+            Err(err) => {
+                eprintln!("lila-gif: {err}");
+                process::exit(1);
+            }
+        },
+    }
+}
+
+async fn serve(themes: &'static Themes, opt: ServeOpt) {
     let app = Router::new()
         .route("/image.gif", get(move |req| image(themes, req)))
         .route("/game.gif", post(move |req| game(themes, req)))
-        .route("/example.gif", get(move || example(themes)));
+        .route("/grid.gif", post(move |req| grid(themes, req)))
+        .route("/games.gif", post(move |req| games(themes, req)))
+        .route("/gallery.gif", get(move |req| gallery(themes, req)))
+        .route("/example.gif", get(move || example(themes)))
+        .route("/capabilities", get(capabilities))
+        .route("/openapi.json", get(openapi))
+        .nest("/v2", v2::routes(themes));
 
     let mut fds = ListenFd::from_env();
     if let Ok(Some(uds)) = fds.take_unix_listener(0) {
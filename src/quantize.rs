@@ -0,0 +1,154 @@
+//! Color quantization, for images that combine cells rendered with
+//! different palettes.
+
+use std::collections::HashMap;
+
+pub type Rgb = [u8; 3];
+
+/// Counts how often each color is used.
+#[derive(Default)]
+pub struct Histogram {
+    counts: HashMap<Rgb, u64>,
+}
+
+impl Histogram {
+    /// Adds the pixels of an indexed image with the given palette, as
+    /// packed RGB triplets.
+    pub fn add_indexed(&mut self, palette: &[u8], pixels: &[u8]) {
+        let mut counts = [0; 256];
+        for &index in pixels {
+            counts[usize::from(index)] += 1;
+        }
+        for (color, count) in palette.chunks_exact(3).zip(counts) {
+            if count > 0 {
+                self.add(rgb(color), count);
+            }
+        }
+    }
+
+    pub fn add(&mut self, color: Rgb, count: u64) {
+        *self.counts.entry(color).or_default() += count;
+    }
+
+    /// Reduces the colors to at most `max_colors`, by repeatedly splitting
+    /// the group of colors with the largest squared error at the weighted
+    /// median of its widest channel.
+    pub fn median_cut(self, max_colors: usize) -> Vec<Rgb> {
+        let colors: Vec<(Rgb, u64)> = self.counts.into_iter().collect();
+        if colors.len() <= max_colors {
+            return colors.into_iter().map(|(color, _)| color).collect();
+        }
+
+        let mut boxes = vec![colors];
+        while boxes.len() < max_colors {
+            let Some((idx, _)) = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .map(|(idx, b)| (idx, squared_error(b)))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            else {
+                break;
+            };
+            let mut colors = boxes.swap_remove(idx);
+            let channel = widest_channel(&colors);
+            colors.sort_unstable_by_key(|(color, _)| color[channel]);
+            let half = colors.iter().map(|(_, count)| count).sum::<u64>() / 2;
+            let mut seen = 0;
+            let split = colors
+                .iter()
+                .position(|(_, count)| {
+                    seen += count;
+                    seen > half
+                })
+                .unwrap_or(0)
+                .clamp(1, colors.len() - 1);
+            let upper = colors.split_off(split);
+            boxes.push(colors);
+            boxes.push(upper);
+        }
+
+        boxes.iter().map(|b| mean(b)).collect()
+    }
+}
+
+fn rgb(color: &[u8]) -> Rgb {
+    [color[0], color[1], color[2]]
+}
+
+fn mean(colors: &[(Rgb, u64)]) -> Rgb {
+    let total: u64 = colors.iter().map(|(_, count)| count).sum();
+    let channel = |c: usize| {
+        let sum: u64 = colors
+            .iter()
+            .map(|(color, count)| u64::from(color[c]) * count)
+            .sum();
+        ((sum + total / 2) / total) as u8
+    };
+    [channel(0), channel(1), channel(2)]
+}
+
+fn squared_error(colors: &[(Rgb, u64)]) -> f64 {
+    let center = mean(colors);
+    colors
+        .iter()
+        .map(|&(color, count)| distance(color, center) as f64 * count as f64)
+        .sum()
+}
+
+fn widest_channel(colors: &[(Rgb, u64)]) -> usize {
+    (0..3)
+        .max_by_key(|&c| {
+            let min = colors.iter().map(|(color, _)| color[c]).min().unwrap_or(0);
+            let max = colors.iter().map(|(color, _)| color[c]).max().unwrap_or(0);
+            max - min
+        })
+        .unwrap_or(0)
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| u32::from(a.abs_diff(b)).pow(2))
+        .sum()
+}
+
+/// Index of the closest color in `palette`.
+pub fn nearest(palette: &[Rgb], color: Rgb) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, &candidate)| distance(candidate, color))
+        .map_or(0, |(idx, _)| idx as u8)
+}
+
+/// Maps every entry of a palette, as packed RGB triplets, to the closest
+/// color in `target`.
+pub fn remap_table(palette: &[u8], target: &[Rgb]) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (entry, color) in table.iter_mut().zip(palette.chunks_exact(3)) {
+        *entry = nearest(target, rgb(color));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median_cut() {
+        let mut histogram = Histogram::default();
+        histogram.add([0, 0, 0], 1000);
+        histogram.add([255, 255, 255], 1000);
+        for i in 0..=255 {
+            histogram.add([i, 0, 0], 1);
+        }
+        let palette = histogram.median_cut(16);
+        assert_eq!(palette.len(), 16);
+        // Heavily used colors are kept exactly.
+        assert!(palette.contains(&[0, 0, 0]));
+        assert!(palette.contains(&[255, 255, 255]));
+        assert_eq!(palette[usize::from(nearest(&palette, [200, 0, 0]))][1], 0);
+    }
+}
@@ -1,14 +1,22 @@
-use std::{iter::FusedIterator, vec};
+use std::{cmp::Ordering, io::Write, iter::FusedIterator, vec};
 
 use bytes::{BufMut, Bytes, BytesMut};
-use gift::{Encoder, block};
-use ndarray::{ArrayView2, ArrayViewMut2, s};
+use gift::{Encoder, block, encode::BlockEnc};
+use ndarray::{ArrayView2, ArrayViewMut2, Zip, s};
 use rusttype::{Font, PositionedGlyph, Scale};
-use shakmaty::{Bitboard, Board, File, Rank, Square, uci::UciMove};
+use shakmaty::{
+    Bitboard, Board, CastlingMode, Chess, Color, File, FromSetup, Piece, Position, Rank, Role,
+    Setup, Square, attacks, san::SanPlus, uci::UciMove,
+};
 
 use crate::{
-    api::{Comment, Coordinates, MoveGlyph, Orientation, PlayerName, RequestBody, RequestParams},
-    theme::{Gradient, Sprite, SpriteKey, Theme, Themes},
+    api::{
+        Blindfold, Comment, Coordinates, GameResult, GameStatus, Ghost, HighlightColor, Highlights,
+        MoveGlyph, Orientation, Player, Region, RequestBody, RequestFrame, RequestParams, Title,
+        Trailer, VisibleSquares,
+    },
+    quantize,
+    theme::{Gradient, Highlight, Sprite, SpriteKey, Theme, Themes},
 };
 
 const GLYPH_BADGE_RADIUS: f32 = 18.0;
@@ -16,31 +24,35 @@ const GLYPH_FONT_SIZE: f32 = 32.0;
 const BAR_PADDING: f32 = 10.0;
 const CLOCK_FONT_SIZE: f32 = 36.0;
 const CLOCK_REGION_PADDING: usize = 20;
+const CAPTION_FONT_SIZE: f32 = 36.0;
+const MATERIAL_FONT_SIZE: f32 = 30.0;
+const MATERIAL_GAP: usize = 12;
+const BANNER_RESULT_FONT_SIZE: f32 = 48.0;
+const BANNER_TEXT_FONT_SIZE: f32 = 30.0;
+const BANNER_LINE_GAP: f32 = 8.0;
+const BANNER_DELAY: u16 = 300;
+const BORDER_FONT_SIZE: f32 = 24.0;
 
 enum RenderState {
     Preamble,
-    Frame(RenderFrame),
+    Frame(Box<RenderFrame>),
     Complete,
 }
 
 struct PlayerBars {
-    white: PlayerName,
-    black: PlayerName,
+    white: Player,
+    black: Player,
 }
 
 impl PlayerBars {
-    fn from(
-        white: Option<PlayerName>,
-        black: Option<PlayerName>,
-        has_clocks: bool,
-    ) -> Option<PlayerBars> {
-        let white_name = white.filter(|s| !s.is_empty());
-        let black_name = black.filter(|s| !s.is_empty());
-
-        if white_name.is_some() || black_name.is_some() || has_clocks {
+    fn from(white: Option<Player>, black: Option<Player>, force: bool) -> Option<PlayerBars> {
+        let white = white.filter(|p| !p.is_empty());
+        let black = black.filter(|p| !p.is_empty());
+
+        if white.is_some() || black.is_some() || force {
             Some(PlayerBars {
-                white: white_name.unwrap_or_default(),
-                black: black_name.unwrap_or_default(),
+                white: white.unwrap_or_default(),
+                black: black.unwrap_or_default(),
             })
         } else {
             None
@@ -48,21 +60,230 @@ impl PlayerBars {
     }
 }
 
+#[derive(Default, PartialEq, Eq)]
+struct Material {
+    surplus: [u8; 5],
+    score: u32,
+}
+
+impl Material {
+    const ROLES: [Role; 5] = [
+        Role::Queen,
+        Role::Rook,
+        Role::Bishop,
+        Role::Knight,
+        Role::Pawn,
+    ];
+
+    fn of(board: &Board, color: Color) -> Material {
+        let mut material = Material::default();
+        let mut score = 0;
+        for (surplus, role) in material.surplus.iter_mut().zip(Material::ROLES) {
+            let ours = board.by_piece(role.of(color)).count() as i32;
+            let theirs = board.by_piece(role.of(!color)).count() as i32;
+            *surplus = (ours - theirs).max(0) as u8;
+            score += (ours - theirs) * role_value(role);
+        }
+        material.score = score.max(0) as u32;
+        material
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Layout {
+    board_width: usize,
+    board_height: usize,
+    border: usize,
+    bar_height: usize,
+    bars: bool,
+    caption: bool,
+}
+
+impl Layout {
+    fn new(theme: &Theme, board: BoardView, bars: bool, caption: bool) -> Layout {
+        Layout {
+            board_width: theme.board_width(board.region),
+            board_height: theme.board_height(board.region),
+            border: board.border(theme),
+            bar_height: theme.bar_height(),
+            bars,
+            caption,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.board_width + 2 * self.border
+    }
+
+    fn height(&self) -> usize {
+        self.caption_top() + if self.caption { self.bar_height } else { 0 }
+    }
+
+    fn board_area_top(&self) -> usize {
+        if self.bars { self.bar_height } else { 0 }
+    }
+
+    fn board_left(&self) -> usize {
+        self.border
+    }
+
+    fn board_top(&self) -> usize {
+        self.board_area_top() + self.border
+    }
+
+    fn bottom_bar_top(&self) -> usize {
+        self.board_top() + self.board_height + self.border
+    }
+
+    fn caption_top(&self) -> usize {
+        self.bottom_bar_top() + if self.bars { self.bar_height } else { 0 }
+    }
+}
+
+struct Banner {
+    result: &'static str,
+    text: String,
+}
+
+impl Banner {
+    fn from(result: GameResult) -> Banner {
+        let winner = result.outcome.winner();
+        Banner {
+            result: match winner {
+                Some(Color::White) => "1-0",
+                Some(Color::Black) => "0-1",
+                None => "½-½",
+            },
+            text: match result.text {
+                Some(text) => text.to_string(),
+                None => {
+                    let loser = winner.map(|winner| color_name(!winner));
+                    let status = result.status.map(|status| match (status, loser) {
+                        (GameStatus::Mate, _) => "Checkmate".to_owned(),
+                        (GameStatus::Resign, Some(loser)) => format!("{loser} resigned"),
+                        (GameStatus::Resign, None) => "Resignation".to_owned(),
+                        (GameStatus::Stalemate, _) => "Stalemate".to_owned(),
+                        (GameStatus::Timeout, Some(loser)) => format!("{loser} left the game"),
+                        (GameStatus::Timeout, None) => "Timeout".to_owned(),
+                        (GameStatus::Outoftime, _) => "Time out".to_owned(),
+                        (GameStatus::Draw, _) => "Draw".to_owned(),
+                    });
+                    let victory =
+                        winner.map(|winner| format!("{} is victorious", color_name(winner)));
+                    match (status, victory) {
+                        (Some(status), Some(victory)) => format!("{status} • {victory}"),
+                        (Some(text), None) | (None, Some(text)) => text,
+                        (None, None) => "Draw".to_owned(),
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct BoardView {
+    pub orientation: Orientation,
+    pub region: Region,
+    pub coordinates: Coordinates,
+    pub blindfold: Blindfold,
+}
+
+impl BoardView {
+    pub fn border(&self, theme: &Theme) -> usize {
+        if self.coordinates == Coordinates::Outside {
+            theme.border_size()
+        } else {
+            0
+        }
+    }
+
+    fn x(&self, square: Square) -> usize {
+        self.orientation.x(self.region, square)
+    }
+
+    fn y(&self, square: Square) -> usize {
+        self.orientation.y(self.region, square)
+    }
+}
+
 #[derive(Default)]
-struct RenderFrame {
+pub struct RenderFrame {
     board: Board,
     highlighted: Bitboard,
+    premoved: Bitboard,
+    highlights: Highlights,
+    destinations: Bitboard,
+    ghost: Option<Ghost>,
+    fogged: Bitboard,
     checked: Bitboard,
     delay: Option<u16>,
     glyph: Option<MoveGlyph>,
     white_clock: Option<u32>,
     black_clock: Option<u32>,
+    caption: Option<String>,
+    banner: Option<Banner>,
 }
 
 impl RenderFrame {
-    fn diff(&self, prev: &RenderFrame) -> Bitboard {
+    pub fn new(frame: &RequestFrame) -> RenderFrame {
+        let setup = frame.fen.as_setup();
+        RenderFrame {
+            highlighted: highlight_uci(frame.last_move),
+            premoved: highlight_uci(frame.premove),
+            highlights: highlight_selected(frame.highlights, frame.selected),
+            destinations: move_destinations(setup, frame.selected),
+            ghost: frame.ghost,
+            fogged: fogged_squares(setup, frame.visible, frame.fog),
+            checked: frame.check.to_square(setup).into_iter().collect(),
+            board: setup.board.clone(),
+            glyph: frame.glyph,
+            white_clock: frame.clock.white,
+            black_clock: frame.clock.black,
+            ..RenderFrame::default()
+        }
+    }
+
+    pub fn animation(
+        frames: Vec<RequestFrame>,
+        default_delay: u16,
+        captions: bool,
+    ) -> Vec<RenderFrame> {
+        let mut render_frames = Vec::with_capacity(frames.len());
+        let mut prev: Option<Setup> = None;
+        for frame in frames {
+            let render_frame = RenderFrame::new(&frame);
+            let setup = frame.fen.into_setup();
+            render_frames.push(RenderFrame {
+                delay: Some(frame.delay.unwrap_or(default_delay)),
+                caption: if captions {
+                    move_caption(prev.as_ref(), &setup, frame.last_move, frame.san)
+                } else {
+                    None
+                },
+                ..render_frame
+            });
+            prev = Some(setup);
+        }
+        render_frames
+    }
+
+    pub fn delay(&self) -> Option<u16> {
+        self.delay
+    }
+
+    pub fn diff(&self, prev: &RenderFrame) -> Bitboard {
         (prev.checked ^ self.checked)
             | (prev.highlighted ^ self.highlighted)
+            | prev.highlights.diff(&self.highlights)
+            | (prev.destinations ^ self.destinations)
+            | (prev.premoved ^ self.premoved)
+            | (prev.fogged ^ self.fogged)
+            | if prev.ghost != self.ghost {
+                ghost_square(prev.ghost) | ghost_square(self.ghost)
+            } else {
+                Bitboard::EMPTY
+            }
             | (prev.board.white() ^ self.board.white())
             | (prev.board.pawns() ^ self.board.pawns())
             | (prev.board.knights() ^ self.board.knights())
@@ -73,94 +294,128 @@ impl RenderFrame {
     }
 }
 
-pub struct Render {
-    theme: &'static Theme,
-    font: &'static Font<'static>,
+pub struct Render<'a> {
+    theme: &'a Theme,
+    font: &'a Font<'a>,
     state: RenderState,
     buffer: Vec<u8>,
     comment: Option<Comment>,
     bars: Option<PlayerBars>,
-    orientation: Orientation,
-    coordinates: Coordinates,
+    layout: Layout,
+    board: BoardView,
     frames: vec::IntoIter<RenderFrame>,
-    kork: bool,
+    trailer: Trailer,
+    loop_count: Option<u16>,
+    material: bool,
     clock_widths: [usize; 2],
+    material_lefts: [usize; 2],
+    material_widths: [usize; 2],
+    caption_width: usize,
 }
 
-impl Render {
-    pub fn new_image(themes: &'static Themes, params: RequestParams) -> Render {
-        let bars = PlayerBars::from(params.white, params.black, false);
+impl<'a> Render<'a> {
+    pub fn new_image(themes: &'a Themes, mut params: RequestParams) -> Render<'a> {
+        let bars = PlayerBars::from(
+            params.white_player(),
+            params.black_player(),
+            params.material,
+        );
         let theme = themes.get(params.theme, params.piece);
+        let board = BoardView {
+            orientation: params.orientation,
+            region: params.region,
+            coordinates: params.coordinates,
+            blindfold: params.blindfold,
+        };
+        let layout = Layout::new(theme, board, bars.is_some(), false);
         Render {
             theme,
             font: themes.font(),
-            buffer: vec![0; theme.height(bars.is_some()) * theme.width()],
+            buffer: vec![0; layout.height() * layout.width()],
             state: RenderState::Preamble,
             comment: params.comment,
             bars,
-            orientation: params.orientation,
-            coordinates: params.coordinates,
-            frames: vec![RenderFrame {
-                highlighted: highlight_uci(params.last_move),
-                checked: params
-                    .check
-                    .to_square(params.fen.as_setup())
-                    .into_iter()
-                    .collect(),
-                board: params.fen.into_setup().board,
-                delay: None,
-                glyph: None,
-                white_clock: None,
-                black_clock: None,
-            }]
-            .into_iter(),
-            kork: false,
+            layout,
+            board,
+            frames: vec![RenderFrame::new(&params.frame())].into_iter(),
+            trailer: Trailer::None,
+            loop_count: Some(0),
+            material: params.material,
             clock_widths: [0; 2],
+            material_lefts: [0; 2],
+            material_widths: [0; 2],
+            caption_width: 0,
         }
     }
 
-    pub fn new_animation(themes: &'static Themes, params: RequestBody) -> Render {
+    pub fn new_animation(themes: &'a Themes, params: RequestBody) -> Render<'a> {
         let has_clocks = params
             .frames
             .iter()
             .any(|f| f.clock.white.is_some() || f.clock.black.is_some());
-        let bars = PlayerBars::from(params.white, params.black, has_clocks);
+        let bars = PlayerBars::from(params.white, params.black, has_clocks || params.material);
+        let has_caption = params.caption || params.frames.iter().any(|f| f.san.is_some());
         let default_delay = params.delay;
         let theme = themes.get(params.theme, params.piece);
+        let board = BoardView {
+            orientation: params.orientation,
+            region: params.region,
+            coordinates: params.coordinates,
+            blindfold: params.blindfold,
+        };
+        let layout = Layout::new(theme, board, bars.is_some(), has_caption);
+
+        let mut frames = RenderFrame::animation(params.frames, default_delay, has_caption);
+
+        // Show the result on top of the final position, with the king of the
+        // winner highlighted.
+        if let Some(result) = params.result
+            && let Some(last) = frames.last()
+        {
+            let winner_king = result
+                .outcome
+                .winner()
+                .and_then(|winner| last.board.king_of(winner));
+            frames.push(RenderFrame {
+                board: last.board.clone(),
+                highlighted: last.highlighted | winner_king.into_iter().collect::<Bitboard>(),
+                premoved: last.premoved,
+                highlights: last.highlights,
+                destinations: last.destinations,
+                ghost: last.ghost,
+                fogged: last.fogged,
+                checked: last.checked,
+                delay: Some(result.delay.unwrap_or(BANNER_DELAY)),
+                glyph: last.glyph,
+                white_clock: last.white_clock,
+                black_clock: last.black_clock,
+                caption: last.caption.clone(),
+                banner: Some(Banner::from(result)),
+            });
+        }
+
         Render {
             theme,
             font: themes.font(),
-            buffer: vec![0; theme.height(bars.is_some()) * theme.width()],
+            buffer: vec![0; layout.height() * layout.width()],
             state: RenderState::Preamble,
             comment: params.comment,
             bars,
-            orientation: params.orientation,
-            coordinates: params.coordinates,
-            frames: params
-                .frames
-                .into_iter()
-                .map(|frame| RenderFrame {
-                    highlighted: highlight_uci(frame.last_move),
-                    checked: frame
-                        .check
-                        .to_square(frame.fen.as_setup())
-                        .into_iter()
-                        .collect(),
-                    board: frame.fen.into_setup().board,
-                    delay: Some(frame.delay.unwrap_or(default_delay)),
-                    glyph: frame.glyph,
-                    white_clock: frame.clock.white,
-                    black_clock: frame.clock.black,
-                })
-                .collect::<Vec<_>>()
-                .into_iter(),
-            kork: true,
+            layout,
+            board,
+            frames: frames.into_iter(),
+            trailer: params.trailer,
+            loop_count: loop_count(params.plays),
+            material: params.material,
             clock_widths: [0; 2],
+            material_lefts: [0; 2],
+            material_widths: [0; 2],
+            caption_width: 0,
         }
     }
 }
 
-impl Iterator for Render {
+impl Iterator for Render<'_> {
     type Item = Bytes;
 
     fn next(&mut self) -> Option<Bytes> {
@@ -174,8 +429,8 @@ impl Iterator for Render {
                 blocks
                     .encode(
                         block::LogicalScreenDesc::default()
-                            .with_screen_height(self.theme.height(self.bars.is_some()) as u16)
-                            .with_screen_width(self.theme.width() as u16)
+                            .with_screen_height(self.layout.height() as u16)
+                            .with_screen_width(self.layout.width() as u16)
                             .with_color_table_config(self.theme.color_table_config()),
                     )
                     .expect("enc logical screen desc");
@@ -184,9 +439,11 @@ impl Iterator for Render {
                     .encode(self.theme.global_color_table().clone())
                     .expect("enc global color table");
 
-                blocks
-                    .encode(block::Application::with_loop_count(0))
-                    .expect("enc application");
+                if let Some(loop_count) = self.loop_count {
+                    blocks
+                        .encode(block::Application::with_loop_count(loop_count))
+                        .expect("enc application");
+                }
 
                 let comment = self
                     .comment
@@ -202,30 +459,38 @@ impl Iterator for Render {
 
                 let frame = self.frames.next().unwrap_or_default();
                 let mut view = ArrayViewMut2::from_shape(
-                    (self.theme.height(self.bars.is_some()), self.theme.width()),
+                    (self.layout.height(), self.layout.width()),
                     &mut self.buffer,
                 )
                 .expect("shape");
 
-                let mut board_view = if let Some(ref bars) = self.bars {
+                if let Some(ref bars) = self.bars {
                     let bar_height = self.theme.bar_height();
-                    let btm_bar_y = bar_height + self.theme.width();
-                    let bar_names = self.orientation.fold(
-                        [(&bars.black as &str, 0), (&bars.white, btm_bar_y)],
-                        [(&bars.white as &str, 0), (&bars.black, btm_bar_y)],
+                    let btm_bar_y = self.layout.bottom_bar_top();
+                    let bar_players = self.board.orientation.fold(
+                        [(&bars.black, 0), (&bars.white, btm_bar_y)],
+                        [(&bars.white, 0), (&bars.black, btm_bar_y)],
                     );
-                    for (name, bar_top) in bar_names {
-                        render_bar(
+                    for (idx, (player, bar_top)) in bar_players.into_iter().enumerate() {
+                        let name_width = render_bar(
                             view.slice_mut(s!(bar_top..(bar_top + bar_height), ..)),
                             self.theme,
                             self.font,
-                            name,
+                            player,
                         );
+                        // Long names can fill the bar, leaving no room for
+                        // material.
+                        self.material_lefts[idx] = if name_width > 0 {
+                            name_width + MATERIAL_GAP
+                        } else {
+                            BAR_PADDING as usize
+                        }
+                        .min(self.layout.width());
                     }
 
-                    let mut clock_buffer = vec![0u8; bar_height * self.theme.width()];
+                    let mut clock_buffer = vec![0u8; bar_height * self.layout.width()];
                     for (idx, (clock, bar_top)) in
-                        clock_positions(&frame, self.orientation, btm_bar_y)
+                        clock_positions(&frame, self.board.orientation, btm_bar_y)
                             .into_iter()
                             .enumerate()
                     {
@@ -236,6 +501,7 @@ impl Iterator for Render {
                                 self.font,
                                 centis,
                                 self.clock_widths[idx],
+                                self.layout.width(),
                             );
                             self.clock_widths[idx] = region_width;
                             let src = ArrayView2::from_shape(
@@ -251,32 +517,91 @@ impl Iterator for Render {
                         }
                     }
 
-                    view.slice_mut(s!(bar_height..(bar_height + self.theme.width()), ..))
-                } else {
-                    view
-                };
+                    if self.material {
+                        for (idx, (color, bar_top)) in bar_colors(self.board.orientation, btm_bar_y)
+                            .into_iter()
+                            .enumerate()
+                        {
+                            let material_left = self.material_lefts[idx];
+                            let region_width = render_material_region(
+                                &mut clock_buffer,
+                                self.theme,
+                                self.font,
+                                &Material::of(&frame.board, color),
+                                color,
+                                self.material_widths[idx],
+                                material_max_width(
+                                    self.layout.width(),
+                                    self.material_lefts[idx],
+                                    self.clock_widths[idx],
+                                ),
+                            );
+                            self.material_widths[idx] = region_width;
+                            if region_width == 0 {
+                                continue;
+                            }
+                            let src = ArrayView2::from_shape(
+                                (bar_height, region_width),
+                                &clock_buffer[..bar_height * region_width],
+                            )
+                            .expect("material src");
+                            view.slice_mut(s!(
+                                bar_top..(bar_top + bar_height),
+                                material_left..(material_left + region_width)
+                            ))
+                            .assign(&src);
+                        }
+                    }
+                }
 
-                if let Some(delay) = frame.delay {
-                    let mut ctrl = block::GraphicControl::default();
-                    ctrl.set_delay_time_cs(delay);
-                    blocks.encode(ctrl).expect("enc graphic control");
+                if self.layout.caption {
+                    let bar_height = self.theme.bar_height();
+                    let caption_top = self.layout.caption_top();
+                    let mut caption_view =
+                        view.slice_mut(s!(caption_top..(caption_top + bar_height), ..));
+                    caption_view.fill(self.theme.bar_color());
+
+                    let mut caption_buffer = vec![0u8; bar_height * self.layout.width()];
+                    let region_width = render_caption_region(
+                        &mut caption_buffer,
+                        self.theme,
+                        self.font,
+                        frame.caption.as_deref().unwrap_or_default(),
+                        self.caption_width,
+                        self.layout.width(),
+                    );
+                    self.caption_width = region_width;
+                    let src = ArrayView2::from_shape(
+                        (bar_height, region_width),
+                        &caption_buffer[..bar_height * region_width],
+                    )
+                    .expect("caption src");
+                    caption_view.slice_mut(s!(.., ..region_width)).assign(&src);
                 }
 
-                render_diff(
-                    board_view.as_slice_mut().expect("continguous"),
+                let board_area_top = self.layout.board_area_top();
+                let mut board_buffer =
+                    vec![0u8; self.layout.board_width * self.layout.board_height];
+                render_board(
+                    view.slice_mut(s!(board_area_top..self.layout.bottom_bar_top(), ..)),
+                    &mut board_buffer,
                     self.theme,
-                    self.orientation,
-                    self.coordinates,
-                    None,
-                    &frame,
                     self.font,
+                    self.board,
+                    &frame,
                 );
 
+                if let Some(delay) = frame.delay {
+                    let mut ctrl = block::GraphicControl::default();
+                    ctrl.set_delay_time_cs(delay);
+                    blocks.encode(ctrl).expect("enc graphic control");
+                }
+
                 blocks
                     .encode(
                         block::ImageDesc::default()
-                            .with_height(self.theme.height(self.bars.is_some()) as u16)
-                            .with_width(self.theme.width() as u16),
+                            .with_height(self.layout.height() as u16)
+                            .with_width(self.layout.width() as u16),
                     )
                     .expect("enc image desc");
 
@@ -284,7 +609,7 @@ impl Iterator for Render {
                 image_data.data_mut().extend_from_slice(&self.buffer);
                 blocks.encode(image_data).expect("enc image data");
 
-                self.state = RenderState::Frame(frame);
+                self.state = RenderState::Frame(Box::new(frame));
             }
             RenderState::Frame(ref prev) => {
                 let mut blocks = Encoder::new(&mut output).into_block_enc();
@@ -292,9 +617,10 @@ impl Iterator for Render {
                 if let Some(frame) = self.frames.next() {
                     if self.bars.is_some() {
                         let bar_height = self.theme.bar_height();
-                        let btm_bar_y = bar_height + self.theme.width();
-                        let prev_clocks = clock_positions(prev, self.orientation, btm_bar_y);
-                        let curr_clocks = clock_positions(&frame, self.orientation, btm_bar_y);
+                        let btm_bar_y = self.layout.bottom_bar_top();
+                        let prev_clocks = clock_positions(prev, self.board.orientation, btm_bar_y);
+                        let curr_clocks =
+                            clock_positions(&frame, self.board.orientation, btm_bar_y);
 
                         for (idx, ((clock, bar_top), (prev_clock, _))) in
                             curr_clocks.into_iter().zip(prev_clocks).enumerate()
@@ -302,19 +628,23 @@ impl Iterator for Render {
                             let Some(centis) = clock.filter(|&c| Some(c) != prev_clock) else {
                                 continue;
                             };
-                            let mut ctrl = block::GraphicControl::default();
-                            ctrl.set_disposal_method(block::DisposalMethod::Keep);
-                            blocks.encode(ctrl).expect("enc clock ctrl");
-
                             let (region_width, clock_left) = render_clock_region(
                                 &mut self.buffer,
                                 self.theme,
                                 self.font,
                                 centis,
                                 self.clock_widths[idx],
+                                self.layout.width(),
                             );
                             self.clock_widths[idx] = region_width;
                             let region_size = bar_height * region_width;
+                            if region_size == 0 {
+                                continue;
+                            }
+
+                            let mut ctrl = block::GraphicControl::default();
+                            ctrl.set_disposal_method(block::DisposalMethod::Keep);
+                            blocks.encode(ctrl).expect("enc clock ctrl");
 
                             blocks
                                 .encode(
@@ -332,31 +662,115 @@ impl Iterator for Render {
                                 .extend_from_slice(&self.buffer[..region_size]);
                             blocks.encode(image_data).expect("enc clock data");
                         }
+
+                        if self.material {
+                            for (idx, (color, bar_top)) in
+                                bar_colors(self.board.orientation, btm_bar_y)
+                                    .into_iter()
+                                    .enumerate()
+                            {
+                                let material = Material::of(&frame.board, color);
+                                if material == Material::of(&prev.board, color) {
+                                    continue;
+                                }
+
+                                let region_width = render_material_region(
+                                    &mut self.buffer,
+                                    self.theme,
+                                    self.font,
+                                    &material,
+                                    color,
+                                    self.material_widths[idx],
+                                    material_max_width(
+                                        self.layout.width(),
+                                        self.material_lefts[idx],
+                                        self.clock_widths[idx],
+                                    ),
+                                );
+                                self.material_widths[idx] = region_width;
+                                let region_size = bar_height * region_width;
+                                if region_size == 0 {
+                                    continue;
+                                }
+
+                                let mut ctrl = block::GraphicControl::default();
+                                ctrl.set_disposal_method(block::DisposalMethod::Keep);
+                                blocks.encode(ctrl).expect("enc material ctrl");
+
+                                blocks
+                                    .encode(
+                                        block::ImageDesc::default()
+                                            .with_left(self.material_lefts[idx] as u16)
+                                            .with_top(bar_top as u16)
+                                            .with_height(bar_height as u16)
+                                            .with_width(region_width as u16),
+                                    )
+                                    .expect("enc material desc");
+
+                                let mut image_data = block::ImageData::new(region_size);
+                                image_data
+                                    .data_mut()
+                                    .extend_from_slice(&self.buffer[..region_size]);
+                                blocks.encode(image_data).expect("enc material data");
+                            }
+                        }
+                    }
+
+                    if self.layout.caption && frame.caption != prev.caption {
+                        let bar_height = self.theme.bar_height();
+                        let region_width = render_caption_region(
+                            &mut self.buffer,
+                            self.theme,
+                            self.font,
+                            frame.caption.as_deref().unwrap_or_default(),
+                            self.caption_width,
+                            self.layout.width(),
+                        );
+                        self.caption_width = region_width;
+                        let region_size = bar_height * region_width;
+
+                        if region_size > 0 {
+                            let mut ctrl = block::GraphicControl::default();
+                            ctrl.set_disposal_method(block::DisposalMethod::Keep);
+                            blocks.encode(ctrl).expect("enc caption ctrl");
+
+                            blocks
+                                .encode(
+                                    block::ImageDesc::default()
+                                        .with_left(0)
+                                        .with_top(self.layout.caption_top() as u16)
+                                        .with_height(bar_height as u16)
+                                        .with_width(region_width as u16),
+                                )
+                                .expect("enc caption desc");
+
+                            let mut image_data = block::ImageData::new(region_size);
+                            image_data
+                                .data_mut()
+                                .extend_from_slice(&self.buffer[..region_size]);
+                            blocks.encode(image_data).expect("enc caption data");
+                        }
                     }
 
                     let mut ctrl = block::GraphicControl::default();
                     ctrl.set_disposal_method(block::DisposalMethod::Keep);
                     ctrl.set_transparent_color(Some(self.theme.transparent_color()));
-                    if let Some(delay) = frame.delay {
+                    if let Some(delay) = frame.delay.filter(|_| frame.banner.is_none()) {
                         ctrl.set_delay_time_cs(delay);
                     }
                     blocks.encode(ctrl).expect("enc graphic control");
 
-                    let ((left, y), (w, h)) = render_diff(
+                    let ((x, y), (w, h)) = render_diff(
                         &mut self.buffer,
                         self.theme,
-                        self.orientation,
-                        self.coordinates,
+                        self.board,
                         Some(prev),
                         &frame,
                         self.font,
                     );
 
-                    let top = y + if self.bars.is_some() {
-                        self.theme.bar_height()
-                    } else {
-                        0
-                    };
+                    let left = x + self.layout.board_left();
+                    let top = y + self.layout.board_top();
 
                     blocks
                         .encode(
@@ -374,36 +788,53 @@ impl Iterator for Render {
                         .extend_from_slice(&self.buffer[..(w * h)]);
                     blocks.encode(image_data).expect("enc image data");
 
-                    self.state = RenderState::Frame(frame);
-                } else {
-                    // Add a black frame at the end, to work around twitter
-                    // cutting off the last frame.
-                    if self.kork {
+                    if let Some(ref banner) = frame.banner {
                         let mut ctrl = block::GraphicControl::default();
                         ctrl.set_disposal_method(block::DisposalMethod::Keep);
-                        ctrl.set_transparent_color(Some(self.theme.transparent_color()));
-                        ctrl.set_delay_time_cs(1);
-                        blocks.encode(ctrl).expect("enc graphic control");
+                        if let Some(delay) = frame.delay {
+                            ctrl.set_delay_time_cs(delay);
+                        }
+                        blocks.encode(ctrl).expect("enc banner ctrl");
+
+                        let (banner_height, banner_width) = render_banner(
+                            &mut self.buffer,
+                            self.theme,
+                            self.font,
+                            banner,
+                            self.layout.width(),
+                            self.layout.board_height,
+                        );
+                        let banner_top = self.layout.board_top()
+                            + (self.layout.board_height - banner_height) / 2;
 
-                        let height = self.theme.height(self.bars.is_some());
-                        let width = self.theme.width();
                         blocks
                             .encode(
                                 block::ImageDesc::default()
                                     .with_left(0)
-                                    .with_top(0)
-                                    .with_height(height as u16)
-                                    .with_width(width as u16),
+                                    .with_top(banner_top as u16)
+                                    .with_height(banner_height as u16)
+                                    .with_width(banner_width as u16),
                             )
-                            .expect("enc image desc");
+                            .expect("enc banner desc");
 
-                        let mut image_data = block::ImageData::new(height * width);
+                        let banner_size = banner_height * banner_width;
+                        let mut image_data = block::ImageData::new(banner_size);
                         image_data
                             .data_mut()
-                            .resize(height * width, self.theme.bar_color());
-                        blocks.encode(image_data).expect("enc image data");
+                            .extend_from_slice(&self.buffer[..banner_size]);
+                        blocks.encode(image_data).expect("enc banner data");
                     }
 
+                    self.state = RenderState::Frame(Box::new(frame));
+                } else {
+                    encode_trailer(
+                        &mut blocks,
+                        self.theme,
+                        self.trailer,
+                        self.layout.height(),
+                        self.layout.width(),
+                    );
+
                     blocks
                         .encode(block::Trailer::default())
                         .expect("enc trailer");
@@ -416,7 +847,75 @@ impl Iterator for Render {
     }
 }
 
-impl FusedIterator for Render {}
+impl FusedIterator for Render<'_> {}
+
+pub fn loop_count(plays: u16) -> Option<u16> {
+    match plays {
+        0 => Some(0),
+        1 => None,
+        plays => Some(plays - 1),
+    }
+}
+
+pub fn encode_trailer<W: Write>(
+    blocks: &mut BlockEnc<W>,
+    theme: &Theme,
+    trailer: Trailer,
+    height: usize,
+    width: usize,
+) {
+    match trailer {
+        Trailer::None => (),
+        Trailer::Dark => {
+            // Add a black frame at the end, to work around twitter
+            // cutting off the last frame.
+            let mut ctrl = block::GraphicControl::default();
+            ctrl.set_disposal_method(block::DisposalMethod::Keep);
+            ctrl.set_transparent_color(Some(theme.transparent_color()));
+            ctrl.set_delay_time_cs(1);
+            blocks.encode(ctrl).expect("enc graphic control");
+
+            blocks
+                .encode(
+                    block::ImageDesc::default()
+                        .with_left(0)
+                        .with_top(0)
+                        .with_height(height as u16)
+                        .with_width(width as u16),
+                )
+                .expect("enc image desc");
+
+            let mut image_data = block::ImageData::new(height * width);
+            image_data
+                .data_mut()
+                .resize(height * width, theme.bar_color());
+            blocks.encode(image_data).expect("enc image data");
+        }
+        Trailer::Repeat => {
+            // Repeat the last frame with a single transparent pixel, to work
+            // around twitter cutting off the last frame without flashing.
+            let mut ctrl = block::GraphicControl::default();
+            ctrl.set_disposal_method(block::DisposalMethod::Keep);
+            ctrl.set_transparent_color(Some(theme.transparent_color()));
+            ctrl.set_delay_time_cs(1);
+            blocks.encode(ctrl).expect("enc graphic control");
+
+            blocks
+                .encode(
+                    block::ImageDesc::default()
+                        .with_left(0)
+                        .with_top(0)
+                        .with_height(1)
+                        .with_width(1),
+                )
+                .expect("enc image desc");
+
+            let mut image_data = block::ImageData::new(1);
+            image_data.data_mut().push(theme.transparent_color());
+            blocks.encode(image_data).expect("enc image data");
+        }
+    }
+}
 
 fn render_glyph_badge(
     square_buffer: &mut ArrayViewMut2<u8>,
@@ -480,39 +979,20 @@ fn render_glyph_badge(
     );
 }
 
-fn render_diff(
+pub fn render_diff(
     buffer: &mut [u8],
     theme: &Theme,
-    orientation: Orientation,
-    coordinates: Coordinates,
+    board: BoardView,
     prev: Option<&RenderFrame>,
     frame: &RenderFrame,
     font: &Font,
 ) -> ((usize, usize), (usize, usize)) {
-    let diff = prev.map_or(Bitboard::FULL, |p| p.diff(frame));
+    let diff = board.region.squares() & prev.map_or(Bitboard::FULL, |p| p.diff(frame));
 
-    let x_min = diff
-        .into_iter()
-        .map(|sq| orientation.x(sq))
-        .min()
-        .unwrap_or(0);
-    let y_min = diff
-        .into_iter()
-        .map(|sq| orientation.y(sq))
-        .min()
-        .unwrap_or(0);
-    let x_max = diff
-        .into_iter()
-        .map(|sq| orientation.x(sq))
-        .max()
-        .unwrap_or(0)
-        + 1;
-    let y_max = diff
-        .into_iter()
-        .map(|sq| orientation.y(sq))
-        .max()
-        .unwrap_or(0)
-        + 1;
+    let x_min = diff.into_iter().map(|sq| board.x(sq)).min().unwrap_or(0);
+    let y_min = diff.into_iter().map(|sq| board.y(sq)).min().unwrap_or(0);
+    let x_max = diff.into_iter().map(|sq| board.x(sq)).max().unwrap_or(0) + 1;
+    let y_max = diff.into_iter().map(|sq| board.y(sq)).max().unwrap_or(0) + 1;
 
     let width = (x_max - x_min) * theme.square();
     let height = (y_max - y_min) * theme.square();
@@ -524,15 +1004,27 @@ fn render_diff(
     }
 
     for sq in diff {
+        let piece = frame.board.piece_at(sq);
+        let ghost = frame.ghost.filter(|ghost| ghost.square == sq);
         let key = SpriteKey {
-            piece: frame.board.piece_at(sq),
+            piece: piece.or(ghost.map(|ghost| ghost.piece)),
+            ghost: piece.is_none() && ghost.is_some(),
+            blindfold: board.blindfold,
+            fog: frame.fogged.contains(sq),
             dark_square: sq.is_dark(),
-            highlight: frame.highlighted.contains(sq),
+            highlight: match frame.highlights.get(sq) {
+                Some(color) => Some(Highlight::Color(color)),
+                None if frame.premoved.contains(sq) => Some(Highlight::Premove),
+                None => frame
+                    .highlighted
+                    .contains(sq)
+                    .then_some(Highlight::LastMove),
+            },
             check: frame.checked.contains(sq),
         };
 
-        let left = (orientation.x(sq) - x_min) * theme.square();
-        let top = (orientation.y(sq) - y_min) * theme.square();
+        let left = (board.x(sq) - x_min) * theme.square();
+        let top = (board.y(sq) - y_min) * theme.square();
 
         let mut square_buffer = view.slice_mut(s!(
             top..(top + theme.square()),
@@ -542,20 +1034,45 @@ fn render_diff(
         match theme.sprite(&key) {
             Sprite::Paste(paste) => square_buffer.assign(&paste),
             Sprite::Fill(fill) => square_buffer.fill(fill),
+            Sprite::Recolor {
+                paste,
+                background,
+                fill,
+            } => {
+                square_buffer.assign(&paste);
+                square_buffer.mapv_inplace(|c| if c == background { fill } else { c });
+            }
         }
 
-        if coordinates == Coordinates::Yes {
-            let coords_scale: Scale = Scale { x: 30.0, y: 30.0 };
-            let (coords_rank, coords_file) = match orientation {
-                Orientation::White => (Rank::First, File::H),
-                Orientation::Black => (Rank::Eighth, File::A),
-            };
-            if sq.rank() == coords_rank {
-                render_file(&mut square_buffer, &sq, &key, theme, font, coords_scale)
-            };
-            if sq.file() == coords_file {
-                render_rank(&mut square_buffer, &sq, &key, theme, font, coords_scale)
-            };
+        if let Some(ghost) = ghost
+            && piece.is_some()
+        {
+            render_ghost_over_piece(&mut square_buffer, theme, &key, ghost.piece);
+        }
+
+        if frame.destinations.contains(sq) {
+            render_move_destination(&mut square_buffer, theme, &key);
+        }
+
+        match board.coordinates {
+            Coordinates::Inside => {
+                let coords_scale: Scale = Scale { x: 30.0, y: 30.0 };
+                let (coords_rank, coords_file) = match board.orientation {
+                    Orientation::White => (board.region.min_rank, board.region.max_file),
+                    Orientation::Black => (board.region.max_rank, board.region.min_file),
+                };
+                if sq.rank() == coords_rank {
+                    render_file(&mut square_buffer, &sq, &key, theme, font, coords_scale)
+                };
+                if sq.file() == coords_file {
+                    render_rank(&mut square_buffer, &sq, &key, theme, font, coords_scale)
+                };
+            }
+            Coordinates::All => {
+                let coords_scale: Scale = Scale { x: 24.0, y: 24.0 };
+                render_square_name(&mut square_buffer, &sq, &key, theme, font, coords_scale)
+            }
+            Coordinates::No | Coordinates::Outside => (),
         }
 
         if let Some(glyph) = frame.glyph
@@ -572,6 +1089,65 @@ fn render_diff(
     )
 }
 
+pub fn render_board(
+    mut view: ArrayViewMut2<u8>,
+    buffer: &mut [u8],
+    theme: &Theme,
+    font: &Font,
+    board: BoardView,
+    frame: &RenderFrame,
+) {
+    let border = board.border(theme);
+    let ((_, _), (width, height)) = render_diff(buffer, theme, board, None, frame, font);
+    let src =
+        ArrayView2::from_shape((height, width), &buffer[..height * width]).expect("board src");
+    view.slice_mut(s!(border..(border + height), border..(border + width)))
+        .assign(&src);
+
+    if border > 0 {
+        view.slice_mut(s!(..border, ..)).fill(theme.bar_color());
+        view.slice_mut(s!((border + height).., ..))
+            .fill(theme.bar_color());
+        view.slice_mut(s!(border..(border + height), ..border))
+            .fill(theme.bar_color());
+        view.slice_mut(s!(border..(border + height), (border + width)..))
+            .fill(theme.bar_color());
+
+        let square = theme.square();
+        let region = board.region;
+        for file in File::ALL
+            .into_iter()
+            .filter(|&f| region.min_file <= f && f <= region.max_file)
+        {
+            let left = border + board.x(Square::from_coords(file, region.min_rank)) * square;
+            render_centered_text(
+                &mut view.slice_mut(s!((border + height).., left..(left + square))),
+                theme,
+                font,
+                &file.to_string(),
+                BORDER_FONT_SIZE,
+                (border as f32 - BORDER_FONT_SIZE) / 2.0,
+                Gradient::TextBar,
+            );
+        }
+        for rank in Rank::ALL
+            .into_iter()
+            .filter(|&r| region.min_rank <= r && r <= region.max_rank)
+        {
+            let top = border + board.y(Square::from_coords(region.min_file, rank)) * square;
+            render_centered_text(
+                &mut view.slice_mut(s!(top..(top + square), ..border)),
+                theme,
+                font,
+                &rank.to_string(),
+                BORDER_FONT_SIZE,
+                (square as f32 - BORDER_FONT_SIZE) / 2.0,
+                Gradient::TextBar,
+            );
+        }
+    }
+}
+
 fn render_file(
     square_buffer: &mut ArrayViewMut2<u8>,
     sq: &Square,
@@ -622,9 +1198,165 @@ fn render_rank(
     );
 }
 
-fn render_bar(mut view: ArrayViewMut2<u8>, theme: &Theme, font: &Font, player_name: &str) {
+/// Blends a ghost over the piece that is already on the square, so that the
+/// piece shows through.
+///
+/// Ghost sprites are pre-blended on empty squares. The opacity of each
+/// pixel is recovered by comparing with the opaque sprite of the same piece.
+fn render_ghost_over_piece(
+    square_buffer: &mut ArrayViewMut2<u8>,
+    theme: &Theme,
+    key: &SpriteKey,
+    piece: Piece,
+) {
+    let sprite = |ghost| match theme.sprite(&SpriteKey {
+        piece: Some(piece),
+        ghost,
+        blindfold: Blindfold::Off,
+        check: false,
+        ..*key
+    }) {
+        Sprite::Paste(paste) | Sprite::Recolor { paste, .. } => Some(paste),
+        Sprite::Fill(_) => None,
+    };
+    let (Some(ghost), Some(opaque)) = (sprite(true), sprite(false)) else {
+        return;
+    };
+
+    let colors = theme.global_color_table().colors();
+    let rgb = |c: u8| {
+        let i = usize::from(c) * 3;
+        [colors[i], colors[i + 1], colors[i + 2]].map(f32::from)
+    };
+    let palette: Vec<_> = colors.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+
+    // Pieces never reach the corners.
+    let background = ghost[(0, 0)];
+    let b = rgb(background);
+    Zip::from(square_buffer)
+        .and(&ghost)
+        .and(&opaque)
+        .for_each(|c, &g, &o| {
+            if g == background {
+                return;
+            }
+            let (g, o, under) = (rgb(g), rgb(o), rgb(*c));
+            let (dot, len) = (0..3).fold((0.0, 0.0), |(dot, len), i| {
+                (
+                    dot + (g[i] - b[i]) * (o[i] - b[i]),
+                    len + (o[i] - b[i]).powi(2),
+                )
+            });
+            let alpha = if len > 0.0 {
+                (dot / len).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+            let blended =
+                [0, 1, 2].map(|i| (alpha * o[i] + (1.0 - alpha) * under[i]).round() as u8);
+            *c = quantize::nearest(&palette, blended);
+        });
+}
+
+fn render_move_destination(square_buffer: &mut ArrayViewMut2<u8>, theme: &Theme, key: &SpriteKey) {
+    let fill = theme.gradient_color(Gradient::HighlightGreen, f32::from(!key.dark_square));
+    // Pieces never reach the corners, so this is the actual fill of the
+    // square, whether plain, highlighted or recolored.
+    let background = square_buffer[(0, 0)];
+
+    // Like lichess: a dot on empty squares, and the corners of occupied
+    // squares for captures. Squares with hidden pieces get a dot, so that
+    // the destinations do not give away where the pieces are.
+    let capture = key
+        .piece
+        .is_some_and(|piece| !key.fog && !key.blindfold.hides(piece.color));
+    let center = (theme.square() - 1) as f32 / 2.0;
+    let corner = theme.square() as f32 / 2.0 * std::f32::consts::SQRT_2;
+    for ((y, x), c) in square_buffer.indexed_iter_mut() {
+        let distance = (x as f32 - center).hypot(y as f32 - center);
+        if capture {
+            if distance >= 0.8 * corner && *c == background {
+                *c = fill;
+            }
+        } else if distance <= 0.2 * corner {
+            *c = fill;
+        }
+    }
+}
+
+fn render_square_name(
+    square_buffer: &mut ArrayViewMut2<u8>,
+    sq: &Square,
+    sprite_key: &SpriteKey,
+    theme: &Theme,
+    font: &Font,
+    font_scale: Scale,
+) {
+    let v_metrics = font.v_metrics(font_scale);
+    let square_name = format!("{sq}");
+    let glyphs = font.layout(
+        &square_name,
+        font_scale,
+        rusttype::point(5.0, theme.square() as f32 + v_metrics.descent),
+    );
+
+    render_text(
+        square_buffer,
+        glyphs,
+        theme,
+        sprite_key.light_dark_gradient(),
+        !sprite_key.dark_square,
+    );
+}
+
+pub fn render_bar(
+    mut view: ArrayViewMut2<u8>,
+    theme: &Theme,
+    font: &Font,
+    player: &Player,
+) -> usize {
     view.fill(theme.bar_color());
 
+    // Each part of the player info is styled separately, so keep a gradient
+    // for every char of the text. Layout yields exactly one glyph per char.
+    let mut text = String::new();
+    let mut gradients = Vec::new();
+    let mut push = |part: &str, gradient: Gradient| {
+        if !text.is_empty() {
+            text.push(' ');
+            gradients.push(Gradient::TextBar);
+        }
+        text.push_str(part);
+        gradients.extend(part.chars().map(|_| gradient));
+    };
+
+    if let Some(title) = player.title {
+        push(
+            title.into(),
+            if title == Title::Bot {
+                Gradient::BotBar
+            } else {
+                Gradient::GoldBar
+            },
+        );
+    }
+    if !player.name.is_empty() {
+        push(&player.name, Gradient::TextBar);
+    }
+    if let Some(ref tag) = player.tag {
+        push(&format!("[{tag}]"), Gradient::TextBar);
+    }
+    if let Some(rating) = player.rating {
+        push(&format!("({rating})"), Gradient::TextBar);
+    }
+    if let Some(diff) = player.rating_diff {
+        match diff.cmp(&0) {
+            Ordering::Greater => push(&format!("+{diff}"), Gradient::RatingGainBar),
+            Ordering::Less => push(&diff.to_string(), Gradient::RatingLossBar),
+            Ordering::Equal => push("±0", Gradient::TextBar),
+        }
+    }
+
     let height = 40.0;
     let scale = Scale {
         x: height,
@@ -632,33 +1364,25 @@ fn render_bar(mut view: ArrayViewMut2<u8>, theme: &Theme, font: &Font, player_na
     };
 
     let v_metrics = font.v_metrics(scale);
-    let mut glyphs = font.layout(
-        player_name,
-        scale,
-        rusttype::point(BAR_PADDING, BAR_PADDING + v_metrics.ascent),
-    );
+    let glyphs: Vec<_> = font
+        .layout(
+            &text,
+            scale,
+            rusttype::point(BAR_PADDING, BAR_PADDING + v_metrics.ascent),
+        )
+        .collect();
+    let name_width = glyphs
+        .iter()
+        .filter_map(|g| g.pixel_bounding_box())
+        .map(|bb| bb.max.x)
+        .max()
+        .unwrap_or(0) as usize;
 
-    let titles = [
-        "GM ", "WGM ", "IM ", "WIM ", "FM ", "WFM ", "NM ", "CM ", "WCM ", "WNM ", "LM ",
-    ];
-    let prefix_color = if player_name.starts_with("BOT ") {
-        Gradient::BotBar
-    } else if titles.iter().any(|title| player_name.starts_with(title)) {
-        Gradient::GoldBar
-    } else {
-        Gradient::TextBar
-    };
-    render_text(
-        &mut view,
-        glyphs
-            .by_ref()
-            .take_while(|g| g.pixel_bounding_box().is_some()),
-        theme,
-        prefix_color,
-        false,
-    );
+    for (glyph, gradient) in glyphs.into_iter().zip(gradients) {
+        render_text(&mut view, Some(glyph), theme, gradient, false);
+    }
 
-    render_text(&mut view, glyphs, theme, Gradient::TextBar, false);
+    name_width
 }
 
 fn format_clock(centis: u32) -> String {
@@ -683,6 +1407,7 @@ fn render_clock_region(
     font: &Font,
     centis: u32,
     min_width: usize,
+    width: usize,
 ) -> (usize, usize) {
     let bar_height = theme.bar_height();
     let scale = Scale {
@@ -709,7 +1434,7 @@ fn render_clock_region(
         .max()
         .unwrap_or(0) as usize;
 
-    let region_width = text_width.max(min_width);
+    let region_width = text_width.max(min_width).min(width);
     let mut view = ArrayViewMut2::from_shape(
         (bar_height, region_width),
         &mut buffer[..bar_height * region_width],
@@ -717,14 +1442,309 @@ fn render_clock_region(
     .expect("clock region shape");
     view.fill(theme.bar_color());
 
-    let text_offset = region_width - text_width;
+    let text_offset = region_width.saturating_sub(text_width);
     let mut text_view = view.slice_mut(s!(.., text_offset as usize..));
     render_text(&mut text_view, glyphs, theme, Gradient::TextBar, false);
 
-    let clock_left = theme.width() - region_width - CLOCK_REGION_PADDING;
+    let clock_left = width.saturating_sub(region_width + CLOCK_REGION_PADDING);
     (region_width, clock_left)
 }
 
+fn render_material_region(
+    buffer: &mut [u8],
+    theme: &Theme,
+    font: &Font,
+    material: &Material,
+    color: Color,
+    min_width: usize,
+    max_width: usize,
+) -> usize {
+    let bar_height = theme.bar_height();
+    let piece_size = theme.bar_piece_size();
+    let pieces = material
+        .surplus
+        .iter()
+        .map(|&n| usize::from(n))
+        .sum::<usize>();
+    let pieces_width = pieces * piece_size;
+
+    let scale = Scale {
+        x: MATERIAL_FONT_SIZE,
+        y: MATERIAL_FONT_SIZE,
+    };
+    let v_metrics = font.v_metrics(scale);
+    let score = if material.score > 0 {
+        format!("+{}", material.score)
+    } else {
+        String::new()
+    };
+    let text_left = if pieces > 0 {
+        pieces_width + MATERIAL_GAP / 2
+    } else {
+        0
+    };
+    let glyphs: Vec<_> = font
+        .layout(
+            &score,
+            scale,
+            rusttype::point(
+                text_left as f32,
+                (bar_height as f32 - MATERIAL_FONT_SIZE) / 2.0 + v_metrics.ascent,
+            ),
+        )
+        .collect();
+    let text_width = glyphs
+        .iter()
+        .filter_map(|g| g.pixel_bounding_box())
+        .map(|bb| bb.max.x)
+        .max()
+        .unwrap_or(0) as usize;
+
+    let region_width = pieces_width.max(text_width).max(min_width).min(max_width);
+    let mut view = ArrayViewMut2::from_shape(
+        (bar_height, region_width),
+        &mut buffer[..bar_height * region_width],
+    )
+    .expect("material region shape");
+    view.fill(theme.bar_color());
+
+    let top = (bar_height - piece_size) / 2;
+    let mut left = 0;
+    for (&surplus, role) in material.surplus.iter().zip(Material::ROLES) {
+        for _ in 0..surplus {
+            if left + piece_size > region_width {
+                break;
+            }
+            view.slice_mut(s!(top..(top + piece_size), left..(left + piece_size)))
+                .assign(&theme.bar_piece(role.of(!color)));
+            left += piece_size;
+        }
+    }
+
+    render_text(&mut view, glyphs, theme, Gradient::TextBar, false);
+
+    region_width
+}
+
+fn material_max_width(width: usize, material_left: usize, clock_width: usize) -> usize {
+    let right = if clock_width > 0 {
+        width.saturating_sub(clock_width + CLOCK_REGION_PADDING)
+    } else {
+        width
+    };
+    right.saturating_sub(material_left)
+}
+
+fn role_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 1,
+        Role::Knight | Role::Bishop => 3,
+        Role::Rook => 5,
+        Role::Queen => 9,
+        Role::King => 0,
+    }
+}
+
+pub fn render_caption_region(
+    buffer: &mut [u8],
+    theme: &Theme,
+    font: &Font,
+    caption: &str,
+    min_width: usize,
+    width: usize,
+) -> usize {
+    let bar_height = theme.bar_height();
+    let scale = Scale {
+        x: CAPTION_FONT_SIZE,
+        y: CAPTION_FONT_SIZE,
+    };
+    let v_metrics = font.v_metrics(scale);
+
+    let glyphs: Vec<_> = font
+        .layout(
+            caption,
+            scale,
+            rusttype::point(
+                BAR_PADDING,
+                (bar_height as f32 - CAPTION_FONT_SIZE) / 2.0 + v_metrics.ascent,
+            ),
+        )
+        .collect();
+    let text_width = glyphs
+        .iter()
+        .filter_map(|g| g.pixel_bounding_box())
+        .map(|bb| bb.max.x)
+        .max()
+        .unwrap_or(0) as usize;
+
+    let region_width = text_width.max(min_width).min(width);
+    let mut view = ArrayViewMut2::from_shape(
+        (bar_height, region_width),
+        &mut buffer[..bar_height * region_width],
+    )
+    .expect("caption region shape");
+    view.fill(theme.bar_color());
+    render_text(&mut view, glyphs, theme, Gradient::TextBar, false);
+
+    region_width
+}
+
+fn render_banner(
+    buffer: &mut [u8],
+    theme: &Theme,
+    font: &Font,
+    banner: &Banner,
+    width: usize,
+    max_height: usize,
+) -> (usize, usize) {
+    let height = (2 * theme.bar_height()).min(max_height);
+    let mut view = ArrayViewMut2::from_shape((height, width), &mut buffer[..height * width])
+        .expect("banner shape");
+    view.fill(theme.bar_color());
+
+    let top =
+        (height as f32 - BANNER_RESULT_FONT_SIZE - BANNER_LINE_GAP - BANNER_TEXT_FONT_SIZE) / 2.0;
+    render_centered_text(
+        &mut view,
+        theme,
+        font,
+        banner.result,
+        BANNER_RESULT_FONT_SIZE,
+        top,
+        Gradient::GoldBar,
+    );
+    render_centered_text(
+        &mut view,
+        theme,
+        font,
+        &banner.text,
+        BANNER_TEXT_FONT_SIZE,
+        top + BANNER_RESULT_FONT_SIZE + BANNER_LINE_GAP,
+        Gradient::TextBar,
+    );
+
+    (height, width)
+}
+
+fn render_centered_text(
+    view: &mut ArrayViewMut2<u8>,
+    theme: &Theme,
+    font: &Font,
+    text: &str,
+    font_size: f32,
+    top: f32,
+    gradient: Gradient,
+) {
+    let scale = Scale {
+        x: font_size,
+        y: font_size,
+    };
+    let v_metrics = font.v_metrics(scale);
+    let text_width = font
+        .layout(text, scale, rusttype::point(0.0, 0.0))
+        .last()
+        .map_or(0.0, |g| {
+            g.position().x + g.unpositioned().h_metrics().advance_width
+        });
+    let left = ((view.ncols() as f32 - text_width) / 2.0).max(0.0);
+    let glyphs = font.layout(text, scale, rusttype::point(left, top + v_metrics.ascent));
+    render_text(view, glyphs, theme, gradient, false);
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "White",
+        Color::Black => "Black",
+    }
+}
+
+fn move_caption(
+    prev: Option<&Setup>,
+    setup: &Setup,
+    last_move: Option<UciMove>,
+    san: Option<SanPlus>,
+) -> Option<String> {
+    let san = san.or_else(|| {
+        let pos = Chess::from_setup(prev?.clone(), CastlingMode::Standard).ok()?;
+        let m = last_move?.to_move(&pos).ok()?;
+        Some(SanPlus::from_move(pos, m))
+    })?;
+
+    let fullmoves = setup.fullmoves.get();
+    Some(match setup.turn {
+        Color::Black => format!("{}. {}", fullmoves, san),
+        Color::White => format!("{}... {}", fullmoves.saturating_sub(1).max(1), san),
+    })
+}
+
+fn highlight_selected(mut highlights: Highlights, selected: Option<Square>) -> Highlights {
+    if let Some(sq) = selected
+        && highlights.get(sq).is_none()
+    {
+        highlights.set(sq, HighlightColor::Green);
+    }
+    highlights
+}
+
+/// Also allows moves of the side that is not to move, as if it were.
+fn position_for(setup: &Setup, color: Color) -> Option<Chess> {
+    let mut setup = setup.clone();
+    if setup.turn != color {
+        setup.turn = color;
+        setup.ep_square = None;
+    }
+    Chess::from_setup(setup, CastlingMode::Standard).ok()
+}
+
+fn fogged_squares(setup: &Setup, visible: Option<VisibleSquares>, fog: Option<Color>) -> Bitboard {
+    if let Some(VisibleSquares(visible)) = visible {
+        return !visible;
+    }
+    let Some(color) = fog else {
+        return Bitboard::EMPTY;
+    };
+
+    // Own pieces and all squares they attack or can move to.
+    let board = &setup.board;
+    let mut visible = board.by_color(color);
+    for sq in board.by_color(color) {
+        if let Some(piece) = board.piece_at(sq) {
+            visible |= attacks::attacks(sq, piece, board.occupied());
+        }
+    }
+    if let Some(pos) = position_for(setup, color) {
+        visible |= pos
+            .legal_moves()
+            .into_iter()
+            .map(|m| m.to())
+            .collect::<Bitboard>();
+    }
+    !visible
+}
+
+fn move_destinations(setup: &Setup, selected: Option<Square>) -> Bitboard {
+    let Some(piece) = selected.and_then(|sq| setup.board.piece_at(sq)) else {
+        return Bitboard::EMPTY;
+    };
+
+    let Some(pos) = position_for(setup, piece.color) else {
+        return Bitboard::EMPTY;
+    };
+
+    pos.legal_moves()
+        .into_iter()
+        .filter(|m| m.from() == selected)
+        .filter_map(|m| match UciMove::from_move(m, CastlingMode::Standard) {
+            UciMove::Normal { to, .. } => Some(to),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ghost_square(ghost: Option<Ghost>) -> Bitboard {
+    ghost.map_or(Bitboard::EMPTY, |ghost| Bitboard::from(ghost.square))
+}
+
 fn highlight_uci(uci: Option<UciMove>) -> Bitboard {
     match uci {
         Some(UciMove::Normal { from, to, .. }) => Bitboard::from(from) | Bitboard::from(to),
@@ -744,6 +1764,13 @@ fn clock_positions(
     )
 }
 
+fn bar_colors(orientation: Orientation, btm_bar_y: usize) -> [(Color, usize); 2] {
+    orientation.fold(
+        [(Color::Black, 0), (Color::White, btm_bar_y)],
+        [(Color::White, 0), (Color::Black, btm_bar_y)],
+    )
+}
+
 fn render_text<'a>(
     view: &mut ArrayViewMut2<'_, u8>,
     glyphs: impl IntoIterator<Item = PositionedGlyph<'a>>,
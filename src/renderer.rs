@@ -0,0 +1,256 @@
+use std::io::{self, Write};
+
+use bytes::Bytes;
+use shakmaty::{fen::Fen, uci::UciMove};
+
+use crate::{
+    api::{
+        CheckSquare, Coordinates, GridAnimationRequest, GridRequest, Highlights, Orientation,
+        Player, RequestBody, RequestParams,
+    },
+    assets::{BoardTheme, PieceSet},
+    grid::{GridRender, GridTooLarge},
+    render::Render,
+    theme::Themes,
+};
+
+enum Request {
+    Image(Box<RequestParams>),
+    Game(Box<RequestBody>),
+    Grid(Box<GridRequest>),
+    Games(Box<GridAnimationRequest>),
+}
+
+/// Builder for rendering a GIF, without going through HTTP.
+///
+/// Starts out as an image of the starting position. Position options like
+/// [`Renderer::position`] only apply to single position images, while
+/// [`Renderer::theme`], [`Renderer::piece`] and the other view options
+/// apply to all kinds of requests.
+///
+/// Choose the kind of request first. Position options panic on other kinds
+/// of requests, and choosing a request after setting position options
+/// panics instead of discarding them.
+pub struct Renderer<'a> {
+    themes: &'a Themes,
+    request: Request,
+    position_options: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(themes: &'a Themes) -> Renderer<'a> {
+        Renderer {
+            themes,
+            request: Request::Image(Box::default()),
+            position_options: false,
+        }
+    }
+
+    /// Render a single position, as `/image.gif` does.
+    pub fn image(self, params: RequestParams) -> Renderer<'a> {
+        self.with_request(Request::Image(Box::new(params)))
+    }
+
+    /// Render an animated game, as `/game.gif` does.
+    pub fn game(self, body: RequestBody) -> Renderer<'a> {
+        self.with_request(Request::Game(Box::new(body)))
+    }
+
+    /// Render several positions on one image, as `/grid.gif` does.
+    pub fn grid(self, grid: GridRequest) -> Renderer<'a> {
+        self.with_request(Request::Grid(Box::new(grid)))
+    }
+
+    /// Animate several games side by side, as `/games.gif` does.
+    pub fn games(self, games: GridAnimationRequest) -> Renderer<'a> {
+        self.with_request(Request::Games(Box::new(games)))
+    }
+
+    /// Set the position of a single position image. Like the other
+    /// position options, panics on other kinds of requests.
+    pub fn position(self, fen: Fen) -> Renderer<'a> {
+        self.with_params(|params| params.fen = fen)
+    }
+
+    pub fn last_move(self, last_move: UciMove) -> Renderer<'a> {
+        self.with_params(|params| params.last_move = Some(last_move))
+    }
+
+    pub fn check(self, check: CheckSquare) -> Renderer<'a> {
+        self.with_params(|params| params.check = check)
+    }
+
+    pub fn highlights(self, highlights: Highlights) -> Renderer<'a> {
+        self.with_params(|params| params.highlights = highlights)
+    }
+
+    pub fn white(self, player: Player) -> Renderer<'a> {
+        self.with_params(|params| params.white = Some(player))
+    }
+
+    pub fn black(self, player: Player) -> Renderer<'a> {
+        self.with_params(|params| params.black = Some(player))
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Renderer<'a> {
+        match self.request {
+            Request::Image(ref mut params) => params.orientation = orientation,
+            Request::Game(ref mut body) => body.orientation = orientation,
+            Request::Grid(ref mut grid) => {
+                for board in &mut grid.boards {
+                    board.orientation = orientation;
+                }
+            }
+            Request::Games(ref mut games) => {
+                for game in &mut games.games {
+                    game.orientation = orientation;
+                }
+            }
+        }
+        self
+    }
+
+    pub fn theme(mut self, theme: BoardTheme) -> Renderer<'a> {
+        match self.request {
+            Request::Image(ref mut params) => params.theme = theme,
+            Request::Game(ref mut body) => body.theme = theme,
+            Request::Grid(ref mut grid) => grid.theme = theme,
+            Request::Games(ref mut games) => games.theme = theme,
+        }
+        self
+    }
+
+    pub fn piece(mut self, piece: PieceSet) -> Renderer<'a> {
+        match self.request {
+            Request::Image(ref mut params) => params.piece = piece,
+            Request::Game(ref mut body) => body.piece = piece,
+            Request::Grid(ref mut grid) => grid.piece = piece,
+            Request::Games(ref mut games) => games.piece = piece,
+        }
+        self
+    }
+
+    pub fn coordinates(mut self, coordinates: Coordinates) -> Renderer<'a> {
+        match self.request {
+            Request::Image(ref mut params) => params.coordinates = coordinates,
+            Request::Game(ref mut body) => body.coordinates = coordinates,
+            Request::Grid(ref mut grid) => grid.coordinates = coordinates,
+            Request::Games(ref mut games) => games.coordinates = coordinates,
+        }
+        self
+    }
+
+    fn with_request(self, request: Request) -> Renderer<'a> {
+        assert!(
+            !self.position_options,
+            "choose the request before setting position options"
+        );
+        Renderer { request, ..self }
+    }
+
+    fn with_params<F>(mut self, f: F) -> Renderer<'a>
+    where
+        F: FnOnce(&mut RequestParams),
+    {
+        match self.request {
+            Request::Image(ref mut params) => f(params),
+            _ => panic!("position options only apply to single position images"),
+        }
+        self.position_options = true;
+        self
+    }
+
+    /// Render the complete GIF into `output`. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if the request is too large.
+    pub fn write_to<W: Write>(self, mut output: W) -> io::Result<()> {
+        let chunks = self
+            .chunks()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        for chunk in chunks {
+            output.write_all(&chunk)?;
+        }
+        output.flush()
+    }
+
+    /// Render the complete GIF into a new buffer.
+    pub fn to_vec(self) -> Result<Vec<u8>, GridTooLarge> {
+        let mut output = Vec::new();
+        for chunk in self.chunks()? {
+            output.extend_from_slice(&chunk);
+        }
+        Ok(output)
+    }
+
+    /// Render the GIF lazily, in chunks that can be streamed as soon as they
+    /// are ready.
+    pub fn chunks(self) -> Result<Chunks<'a>, GridTooLarge> {
+        Ok(match self.request {
+            Request::Image(params) => {
+                Chunks::Render(Box::new(Render::new_image(self.themes, *params)))
+            }
+            Request::Game(body) => {
+                Chunks::Render(Box::new(Render::new_animation(self.themes, *body)))
+            }
+            Request::Grid(grid) => {
+                Chunks::Grid(Box::new(GridRender::new_image(self.themes, *grid)?))
+            }
+            Request::Games(games) => {
+                Chunks::Grid(Box::new(GridRender::new_animation(self.themes, *games)?))
+            }
+        })
+    }
+}
+
+/// Chunks of an encoded GIF, see [`Renderer::chunks`].
+pub enum Chunks<'a> {
+    Render(Box<Render<'a>>),
+    Grid(Box<GridRender<'a>>),
+}
+
+impl Iterator for Chunks<'_> {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        match self {
+            Chunks::Render(render) => render.next(),
+            Chunks::Grid(grid) => grid.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+
+    use super::*;
+
+    static THEMES: LazyLock<Themes> = LazyLock::new(Themes::new);
+
+    #[test]
+    fn test_position_options() {
+        let renderer = Renderer::new(&THEMES)
+            .image(RequestParams::default())
+            .position(Fen::default())
+            .last_move("e2e4".parse().unwrap())
+            .theme(BoardTheme::Green);
+        assert!(
+            matches!(renderer.request, Request::Image(ref params) if params.last_move.is_some())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "position options only apply to single position images")]
+    fn test_position_options_on_game() {
+        let _ = Renderer::new(&THEMES)
+            .game(RequestBody::example())
+            .last_move("e2e4".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "choose the request before setting position options")]
+    fn test_request_after_position_options() {
+        let _ = Renderer::new(&THEMES)
+            .position(Fen::default())
+            .game(RequestBody::example());
+    }
+}
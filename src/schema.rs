@@ -0,0 +1,460 @@
+//! Machine readable descriptions of the HTTP API, generated from the request
+//! types, the enums in [`crate::assets`] and the names accepted by the
+//! custom deserializers in [`crate::api`].
+
+use std::borrow::Cow;
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings, json_schema};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    api::{
+        BannerText, CheckSquare, Comment, Coordinates, GalleryParams, GridAnimationRequest,
+        GridBoards, GridGames, GridRequest, HighlightColor, Highlights, Player, PlayerInfo,
+        PlayerName, PlayerTag, RequestBody, RequestParams, VisibleSquares,
+    },
+    assets::{BoardTheme, PieceSet},
+    grid::MAX_SPACING,
+};
+
+impl JsonSchema for Player {
+    fn schema_name() -> Cow<'static, str> {
+        "Player".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Player name, or object with details.",
+            "oneOf": [
+                generator.subschema_for::<PlayerName>(),
+                generator.subschema_for::<PlayerInfo>(),
+            ],
+        })
+    }
+}
+
+impl JsonSchema for CheckSquare {
+    fn schema_name() -> Cow<'static, str> {
+        "CheckSquare".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let names: Vec<&str> = CheckSquare::NAMES.iter().map(|&(name, _)| name).collect();
+        json_schema!({
+            "description": "Highlight the king of the side to move, or the given square.",
+            "oneOf": [
+                { "type": "boolean" },
+                { "type": "string", "enum": names },
+                { "type": "string", "pattern": "^[a-h][1-8]$" },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for Highlights {
+    fn schema_name() -> Cow<'static, str> {
+        "Highlights".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Squares with highlight colors.",
+            "oneOf": [
+                {
+                    "type": "object",
+                    "propertyNames": { "pattern": "^[a-h][1-8]$" },
+                    "additionalProperties": generator.subschema_for::<HighlightColor>(),
+                },
+                {
+                    "type": "string",
+                    "examples": ["e4:red,d5:blue"],
+                },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for VisibleSquares {
+    fn schema_name() -> Cow<'static, str> {
+        "VisibleSquares".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Bitboard of squares to show (bit 0 is a1, bit 63 is h8). Numbers must be at most 2^53 - 1 to stay exact, larger bitboards must be strings.",
+            "oneOf": [
+                {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": VisibleSquares::MAX_SAFE_INTEGER,
+                },
+                {
+                    "type": "string",
+                    "pattern": "^([0-9]{1,20}|0x[0-9a-fA-F]{1,16})$",
+                    "examples": ["65535", "0xffff000000000000"],
+                },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for Coordinates {
+    fn schema_name() -> Cow<'static, str> {
+        "Coordinates".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let names: Vec<&str> = Coordinates::NAMES.iter().map(|&(name, _)| name).collect();
+        json_schema!({
+            "oneOf": [
+                { "type": "boolean" },
+                { "type": "string", "enum": names },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for BoardTheme {
+    fn schema_name() -> Cow<'static, str> {
+        "BoardTheme".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let names: Vec<&str> = BoardTheme::ALL
+            .into_iter()
+            .flat_map(|theme| [theme.name()].into_iter().chain(theme.aliases()))
+            .collect();
+        json_schema!({
+            "description": "Board theme. Most names are aliases of the six rendered themes.",
+            "type": "string",
+            "enum": names,
+        })
+    }
+}
+
+impl JsonSchema for PieceSet {
+    fn schema_name() -> Cow<'static, str> {
+        "PieceSet".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let names: Vec<&str> = PieceSet::ALL
+            .into_iter()
+            .flat_map(|piece| [piece.name()].into_iter().chain(piece.aliases()))
+            .collect();
+        json_schema!({
+            "type": "string",
+            "enum": names,
+        })
+    }
+}
+
+fn gif_response(description: &str) -> Value {
+    json!({
+        "200": {
+            "description": description,
+            "content": { "image/gif": { "schema": { "type": "string", "format": "binary" } } },
+        },
+        "400": { "description": "Invalid request." },
+    })
+}
+
+fn json_response(description: &str) -> Value {
+    json!({
+        "200": {
+            "description": description,
+            "content": { "application/json": { "schema": { "type": "object" } } },
+        },
+    })
+}
+
+fn post_gif(summary: &str, body: Schema) -> Value {
+    let mut responses = gif_response("Rendered GIF.");
+    responses["422"] = json!({ "description": "Request body does not match the schema." });
+    json!({
+        "post": {
+            "summary": summary,
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": body } },
+            },
+            "responses": responses,
+        },
+    })
+}
+
+/// Describes each property of a query string type as a parameter.
+fn query_parameters<T: JsonSchema>(generator: &mut SchemaGenerator) -> Vec<Value> {
+    let schema = generator.root_schema_for::<T>();
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| {
+            json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&name.as_str()),
+                "schema": schema,
+            })
+        })
+        .collect()
+}
+
+/// OpenAPI 3.1 description of all routes.
+pub fn openapi() -> Value {
+    let mut generator = SchemaSettings::draft2020_12()
+        .with(|settings| {
+            settings.definitions_path = "#/components/schemas/".into();
+            settings.meta_schema = None;
+        })
+        .for_deserialize()
+        .into_generator();
+
+    let game = generator.subschema_for::<RequestBody>();
+    let grid = generator.subschema_for::<GridRequest>();
+    let games = generator.subschema_for::<GridAnimationRequest>();
+
+    let mut paths = Map::new();
+    paths.insert(
+        "/image.gif".to_owned(),
+        json!({
+            "get": {
+                "summary": "Render a single position.",
+                "parameters": query_parameters::<RequestParams>(&mut generator),
+                "responses": gif_response("Rendered GIF."),
+            },
+        }),
+    );
+    paths.insert(
+        "/game.gif".to_owned(),
+        post_gif("Render an animated game.", game),
+    );
+    paths.insert(
+        "/grid.gif".to_owned(),
+        post_gif("Render a grid of positions.", grid),
+    );
+    paths.insert(
+        "/games.gif".to_owned(),
+        post_gif("Render a grid of animated games.", games),
+    );
+    paths.insert(
+        "/gallery.gif".to_owned(),
+        json!({
+            "get": {
+                "summary": "Render one position in every piece set on a board theme, or in every board theme with a piece set.",
+                "description": "The colors of all boards are reduced to one shared color table, so that the gallery is a single image, at the cost of slight color shifts.",
+                "parameters": query_parameters::<GalleryParams>(&mut generator),
+                "responses": gif_response("Rendered GIF."),
+            },
+        }),
+    );
+    paths.insert(
+        "/example.gif".to_owned(),
+        json!({
+            "get": {
+                "summary": "Render an example game.",
+                "responses": gif_response("Rendered GIF."),
+            },
+        }),
+    );
+    paths.insert(
+        "/capabilities".to_owned(),
+        json!({
+            "get": {
+                "summary": "List themes, piece sets, limits and output formats.",
+                "responses": json_response("Capabilities."),
+            },
+        }),
+    );
+    paths.insert(
+        "/openapi.json".to_owned(),
+        json!({
+            "get": {
+                "summary": "This document.",
+                "responses": json_response("OpenAPI document."),
+            },
+        }),
+    );
+
+    // Versioned routes take the same requests, but strictly.
+    for path in [
+        "/image.gif",
+        "/game.gif",
+        "/grid.gif",
+        "/games.gif",
+        "/gallery.gif",
+    ] {
+        let mut item = paths[path].clone();
+        for operation in item
+            .as_object_mut()
+            .into_iter()
+            .flat_map(|ops| ops.values_mut())
+        {
+            operation["description"] = json!(
+                "Rejects unknown fields. Use of deprecated theme and piece set aliases, and of flat player fields like `whiteTitle`, is reported in `X-Lila-Gif-Warning` headers."
+            );
+            if let Some(parameters) = operation
+                .get_mut("parameters")
+                .and_then(Value::as_array_mut)
+            {
+                let mut nested = Vec::new();
+                for parameter in parameters.iter_mut() {
+                    let name = parameter["name"].as_str().unwrap_or_default();
+                    if let Some(&(_, replacement)) = RequestParams::PLAYER_FIELDS
+                        .iter()
+                        .find(|&&(flat, _)| flat == name)
+                    {
+                        let mut replaced = parameter.clone();
+                        replaced["name"] = json!(replacement);
+                        nested.push(replaced);
+                        parameter["deprecated"] = json!(true);
+                    }
+                }
+                parameters.extend(nested);
+            }
+            operation["responses"]["422"] =
+                json!({ "description": "Unknown fields or invalid values." });
+        }
+        paths.insert(format!("/v2{path}"), item);
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "lila-gif",
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": { "schemas": generator.take_definitions(true) },
+    })
+}
+
+#[derive(Serialize)]
+pub struct ThemeInfo {
+    pub name: &'static str,
+    pub aliases: Vec<&'static str>,
+}
+
+/// Maximum lengths of strings are in bytes.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Limits {
+    pub player_name: usize,
+    pub player_tag: usize,
+    pub comment: usize,
+    pub caption: usize,
+    pub grid_boards: usize,
+    pub grid_games: usize,
+    pub grid_spacing: usize,
+}
+
+#[derive(Serialize)]
+pub struct Capabilities {
+    pub themes: Vec<ThemeInfo>,
+    pub pieces: Vec<ThemeInfo>,
+    pub limits: Limits,
+    pub formats: Vec<&'static str>,
+}
+
+/// Themes, piece sets, limits and output formats supported by this build.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        themes: BoardTheme::ALL
+            .into_iter()
+            .map(|theme| ThemeInfo {
+                name: theme.name(),
+                aliases: theme.aliases().collect(),
+            })
+            .collect(),
+        pieces: PieceSet::ALL
+            .into_iter()
+            .map(|piece| ThemeInfo {
+                name: piece.name(),
+                aliases: piece.aliases().collect(),
+            })
+            .collect(),
+        limits: Limits {
+            player_name: PlayerName::new().capacity(),
+            player_tag: PlayerTag::new().capacity(),
+            comment: Comment::new().capacity(),
+            caption: BannerText::new().capacity(),
+            grid_boards: GridBoards::new().capacity(),
+            grid_games: GridGames::new().capacity(),
+            grid_spacing: MAX_SPACING,
+        },
+        formats: vec!["image/gif"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(r)) = map.get("$ref") {
+                    out.push(r);
+                }
+                map.values().for_each(|v| refs(v, out));
+            }
+            Value::Array(values) => values.iter().for_each(|v| refs(v, out)),
+            _ => (),
+        }
+    }
+
+    #[test]
+    fn test_openapi_refs_resolve() {
+        let doc = openapi();
+        let mut found = Vec::new();
+        refs(&doc, &mut found);
+        assert!(!found.is_empty());
+        for r in found {
+            let name = r.strip_prefix("#/components/schemas/").expect("local ref");
+            assert!(doc["components"]["schemas"][name].is_object(), "{r}");
+        }
+        assert!(
+            doc["components"]["schemas"]["BoardTheme"]["enum"]
+                .as_array()
+                .expect("theme names")
+                .contains(&json!("leather"))
+        );
+        let parameters = doc["paths"]["/v2/image.gif"]["get"]["parameters"]
+            .as_array()
+            .expect("parameters");
+        let parameter = |name: &str| {
+            parameters
+                .iter()
+                .find(|p| p["name"] == name)
+                .unwrap_or_else(|| panic!("{name}"))
+        };
+        assert_eq!(parameter("whiteTitle")["deprecated"], true);
+        assert!(parameter("white.title").get("deprecated").is_none());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = serde_json::to_value(capabilities()).expect("serialize");
+        assert_eq!(capabilities["themes"][1]["name"], "brown");
+        assert!(
+            capabilities["themes"][1]["aliases"]
+                .as_array()
+                .expect("aliases")
+                .contains(&json!("maple"))
+        );
+        assert_eq!(
+            capabilities["pieces"][20],
+            json!({ "name": "kiwen-suwi", "aliases": ["kiwensuwi"] })
+        );
+        assert_eq!(capabilities["limits"]["gridBoards"], 64);
+    }
+}
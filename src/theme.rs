@@ -4,33 +4,51 @@ use rusttype::Font;
 use shakmaty::{Piece, Role};
 
 use crate::{
-    api::MoveGlyph,
+    api::{Blindfold, HighlightColor, MoveGlyph, Region},
     assets::{BoardTheme, ByBoardTheme, ByPieceSet, PieceSet, sprite_data},
 };
 
 const SQUARE: usize = 90;
+const BAR_PIECE: usize = 30;
+const BAR_PIECE_ROW: usize = 7;
+const GHOST_ROW: usize = 8;
+const BLINDFOLD_ROW: usize = 14;
+const GRADIENT_ROW: usize = 16;
 
 pub enum Sprite<'a> {
     Paste(ArrayView2<'a, u8>),
     Fill(u8),
+    Recolor {
+        paste: ArrayView2<'a, u8>,
+        background: u8,
+        fill: u8,
+    },
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Gradient {
-    LightDark = 7,
-    LightHighlightDarkHighlight = 8,
-    TransparentTransparent = 9,
-    TextBar = 10,
-    GoldBar = 11,
-    BotBar = 12,
-    GlyphGood = 13,
-    GlyphBrilliant = 14,
-    GlyphMistake = 15,
-    GlyphBlunder = 16,
-    GlyphInteresting = 17,
-    GlyphDubious = 18,
-    GlyphOnlyMove = 19,
-    GlyphZugzwang = 20,
+    LightDark = 0,
+    LightHighlightDarkHighlight = 1,
+    TransparentTransparent = 2,
+    TextBar = 3,
+    GoldBar = 4,
+    BotBar = 5,
+    GlyphGood = 6,
+    GlyphBrilliant = 7,
+    GlyphMistake = 8,
+    GlyphBlunder = 9,
+    GlyphInteresting = 10,
+    GlyphDubious = 11,
+    GlyphOnlyMove = 12,
+    GlyphZugzwang = 13,
+    RatingGainBar = 14,
+    RatingLossBar = 15,
+    HighlightGreen = 16,
+    HighlightRed = 17,
+    HighlightBlue = 18,
+    HighlightYellow = 19,
+    HighlightPremove = 20,
+    Fog = 21,
 }
 
 impl From<MoveGlyph> for Gradient {
@@ -48,20 +66,43 @@ impl From<MoveGlyph> for Gradient {
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Highlight {
+    LastMove,
+    Premove,
+    Color(HighlightColor),
+}
+
+impl Highlight {
+    fn gradient(self) -> Gradient {
+        match self {
+            Highlight::LastMove => Gradient::LightHighlightDarkHighlight,
+            Highlight::Premove => Gradient::HighlightPremove,
+            Highlight::Color(HighlightColor::Green) => Gradient::HighlightGreen,
+            Highlight::Color(HighlightColor::Red) => Gradient::HighlightRed,
+            Highlight::Color(HighlightColor::Blue) => Gradient::HighlightBlue,
+            Highlight::Color(HighlightColor::Yellow) => Gradient::HighlightYellow,
+        }
+    }
+}
+
 pub struct SpriteKey {
     pub piece: Option<Piece>,
+    pub ghost: bool,
+    pub blindfold: Blindfold,
+    pub fog: bool,
     pub dark_square: bool,
-    pub highlight: bool,
+    pub highlight: Option<Highlight>,
     pub check: bool,
 }
 
 impl SpriteKey {
     pub fn light_dark_gradient(&self) -> Gradient {
-        if self.highlight {
-            Gradient::LightHighlightDarkHighlight
-        } else {
-            Gradient::LightDark
+        if self.fog {
+            return Gradient::Fog;
         }
+        self.highlight
+            .map_or(Gradient::LightDark, Highlight::gradient)
     }
 }
 
@@ -81,7 +122,7 @@ impl Theme {
 
         let frame = decoder.next().expect("frame").expect("decode frame");
         let sprite = Array2::from_shape_vec(
-            (SQUARE * (7 + 14), SQUARE * 8),
+            (usize::from(frame.height()), usize::from(frame.width())),
             frame.image_data.data().to_owned(),
         )
         .expect("from shape");
@@ -101,16 +142,24 @@ impl Theme {
         self.square() * 8
     }
 
+    pub fn board_width(&self, region: Region) -> usize {
+        self.square() * region.files()
+    }
+
+    pub fn board_height(&self, region: Region) -> usize {
+        self.square() * region.ranks()
+    }
+
     pub fn bar_height(&self) -> usize {
         60
     }
 
-    pub fn height(&self, bars: bool) -> usize {
-        if bars {
-            self.width() + 2 * self.bar_height()
-        } else {
-            self.width()
-        }
+    pub fn border_size(&self) -> usize {
+        36
+    }
+
+    pub fn bar_piece_size(&self) -> usize {
+        BAR_PIECE
     }
 
     pub fn color_table_config(&self) -> ColorTableConfig {
@@ -124,7 +173,7 @@ impl Theme {
     pub fn gradient_color(&self, gradient: Gradient, intensity: f32) -> u8 {
         let max_x = ((SQUARE * 8) - 1) as f32;
         let x = ((1.0 - intensity.clamp(0.0, 1.0)) * max_x) as usize;
-        let y = (gradient as usize) * SQUARE;
+        let y = (GRADIENT_ROW + gradient as usize) * SQUARE;
         self.sprite[(y, x)]
     }
 
@@ -140,40 +189,86 @@ impl Theme {
         self.gradient_color(Gradient::from(glyph), 0.0)
     }
 
+    pub fn bar_piece(&self, piece: Piece) -> ArrayView2<'_, u8> {
+        let x = BAR_PIECE * (5 * usize::from(piece.color.is_black()) + piece.role as usize - 1);
+        let y = SQUARE * BAR_PIECE_ROW;
+        self.sprite
+            .slice(s!(y..(y + BAR_PIECE), x..(x + BAR_PIECE)))
+    }
+
     pub fn sprite<'a>(&'a self, key: &SpriteKey) -> Sprite<'a> {
         match *key {
+            // Fog hides pieces and highlights.
+            SpriteKey {
+                fog: true,
+                dark_square,
+                ..
+            } => Sprite::Fill(self.gradient_color(Gradient::Fog, f32::from(!dark_square))),
             SpriteKey {
                 piece: Some(piece),
+                ghost: false,
+                blindfold,
                 dark_square,
                 highlight,
                 check,
+                ..
+            } if blindfold.hides(piece.color) => {
+                let x = 4 * usize::from(piece.role == Role::King && check)
+                    + 2 * usize::from(highlight == Some(Highlight::LastMove))
+                    + usize::from(dark_square);
+                let y = BLINDFOLD_ROW + usize::from(blindfold == Blindfold::Discs);
+                self.square_sprite(x, y, highlight, dark_square)
+            }
+            SpriteKey {
+                piece: Some(piece),
+                ghost,
+                dark_square,
+                highlight,
+                check,
+                ..
             } => {
                 let x = 4 * usize::from(piece.color.is_white())
-                    + 2 * usize::from(highlight)
+                    + 2 * usize::from(highlight == Some(Highlight::LastMove))
                     + usize::from(dark_square);
-                let y = if piece.role == Role::King && check {
+                let y = if ghost {
+                    GHOST_ROW + piece.role as usize - 1
+                } else if piece.role == Role::King && check {
                     6
                 } else {
                     piece.role as usize - 1
                 };
-                Sprite::Paste(self.sprite.slice(s!(
-                    (SQUARE * y)..(SQUARE + SQUARE * y),
-                    (SQUARE * x)..(SQUARE + SQUARE * x)
-                )))
+                self.square_sprite(x, y, highlight, dark_square)
             }
             SpriteKey {
-                highlight: false,
+                piece: None,
                 dark_square,
                 ..
-            } => Sprite::Fill(self.gradient_color(Gradient::LightDark, f32::from(!dark_square))),
-            SpriteKey {
-                highlight: true,
-                dark_square,
-                ..
-            } => Sprite::Fill(self.gradient_color(
-                Gradient::LightHighlightDarkHighlight,
-                f32::from(!dark_square),
-            )),
+            } => Sprite::Fill(
+                self.gradient_color(key.light_dark_gradient(), f32::from(!dark_square)),
+            ),
+        }
+    }
+
+    fn square_sprite(
+        &self,
+        x: usize,
+        y: usize,
+        highlight: Option<Highlight>,
+        dark_square: bool,
+    ) -> Sprite<'_> {
+        let paste = self.sprite.slice(s!(
+            (SQUARE * y)..(SQUARE + SQUARE * y),
+            (SQUARE * x)..(SQUARE + SQUARE * x)
+        ));
+        match highlight {
+            // Sprites are only pre-rendered on plain and last move squares.
+            // For other colors, swap the background.
+            Some(highlight @ (Highlight::Premove | Highlight::Color(_))) => Sprite::Recolor {
+                paste,
+                background: self.gradient_color(Gradient::LightDark, f32::from(!dark_square)),
+                fill: self.gradient_color(highlight.gradient(), f32::from(!dark_square)),
+            },
+            _ => Sprite::Paste(paste),
         }
     }
 }
@@ -183,6 +278,12 @@ pub struct Themes {
     font: Font<'static>,
 }
 
+impl Default for Themes {
+    fn default() -> Themes {
+        Themes::new()
+    }
+}
+
 impl Themes {
     pub fn new() -> Themes {
         let font_data = include_bytes!("../theme/font/NotoSans-Regular.ttf") as &[u8];
@@ -0,0 +1,391 @@
+//! Versioned HTTP API under `/v2/`.
+//!
+//! Requests are the same as on the legacy routes, but unknown fields are
+//! rejected instead of silently ignored, so that typos like `lastmove` do
+//! not go unnoticed. Use of deprecated theme and piece set aliases, and of
+//! the flat player fields like `whiteTitle`, is reported in
+//! `X-Lila-Gif-Warning` headers.
+
+use std::{borrow::Cow, convert::Infallible};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    http::{
+        HeaderMap, HeaderValue, StatusCode, Uri,
+        header::{CONTENT_TYPE, HeaderName},
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use futures::stream;
+use lila_gif::{
+    GridRender, GridTooLarge, Render, Themes,
+    api::{GalleryParams, GridAnimationRequest, GridRequest, RequestBody, RequestParams},
+    assets::{BoardTheme, PieceSet},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+
+pub const VERSION: &str = "2";
+
+static API_VERSION: HeaderName = HeaderName::from_static("api-version");
+static WARNING: HeaderName = HeaderName::from_static("x-lila-gif-warning");
+
+/// A request that was accepted, with warnings to report to the client.
+struct Strict<T> {
+    value: T,
+    warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+enum Rejection {
+    UnsupportedMediaType,
+    TooLarge(GridTooLarge),
+    Syntax(String),
+    Data(String),
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Rejection::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "expected request with content type application/json".to_owned(),
+            ),
+            Rejection::TooLarge(err) => (StatusCode::BAD_REQUEST, err.to_string()),
+            Rejection::Syntax(message) => (StatusCode::BAD_REQUEST, message),
+            Rejection::Data(message) => (StatusCode::UNPROCESSABLE_ENTITY, message),
+        };
+        (
+            status,
+            [(API_VERSION.clone(), HeaderValue::from_static(VERSION))],
+            message,
+        )
+            .into_response()
+    }
+}
+
+fn alias_warning(field: &str, name: &str) -> Option<String> {
+    let canonical = match field {
+        "theme" => name.parse::<BoardTheme>().ok()?.name(),
+        "piece" => name.parse::<PieceSet>().ok()?.name(),
+        _ => return None,
+    };
+    (canonical != name).then(|| format!("{field} {name} is a deprecated alias of {canonical}"))
+}
+
+/// Dotted path of a field, like `frames.1.lastmove`.
+fn field_path(path: &serde_ignored::Path) -> String {
+    match *path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => join_path(parent, &index.to_string()),
+        serde_ignored::Path::Map { parent, ref key } => join_path(parent, key),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+fn join_path(parent: &serde_ignored::Path, name: &str) -> String {
+    match field_path(parent) {
+        parent if parent.is_empty() => name.to_owned(),
+        parent => format!("{parent}.{name}"),
+    }
+}
+
+fn unknown_fields(unknown: Vec<String>) -> Result<(), Rejection> {
+    match unknown.as_slice() {
+        [] => Ok(()),
+        [field] => Err(Rejection::Data(format!("unknown field `{field}`"))),
+        fields => Err(Rejection::Data(format!(
+            "unknown fields `{}`",
+            fields.join("`, `")
+        ))),
+    }
+}
+
+fn from_query<T: DeserializeOwned>(uri: &Uri) -> Result<Strict<T>, Rejection> {
+    // Accept nested player fields like white.title in place of the flat
+    // legacy names, which are still understood but deprecated.
+    let mut warnings = Vec::new();
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes()) {
+        warnings.extend(alias_warning(&key, &value));
+        let field = RequestParams::PLAYER_FIELDS
+            .iter()
+            .find(|&&(flat, nested)| key == flat || key == nested);
+        match field {
+            Some(&(flat, nested)) => {
+                if key == flat {
+                    warnings.push(format!("{flat} is deprecated, use {nested}"));
+                }
+                query.append_pair(flat, &value);
+            }
+            None => {
+                query.append_pair(&key, &value);
+            }
+        }
+    }
+    let query = query.finish();
+
+    let mut unknown = Vec::new();
+    let value = serde_ignored::deserialize(
+        serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes())),
+        |path| unknown.push(field_path(&path)),
+    )
+    .map_err(|err| Rejection::Data(err.to_string()))?;
+    unknown_fields(unknown)?;
+
+    Ok(Strict { value, warnings })
+}
+
+fn from_json<T: DeserializeOwned>(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Strict<T>, Rejection> {
+    let json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if !json {
+        return Err(Rejection::UnsupportedMediaType);
+    }
+
+    let json_rejection = |err: serde_json::Error| match err.classify() {
+        serde_json::error::Category::Data => Rejection::Data(err.to_string()),
+        _ => Rejection::Syntax(err.to_string()),
+    };
+    let mut unknown = Vec::new();
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let value =
+        serde_ignored::deserialize(&mut deserializer, |path| unknown.push(field_path(&path)))
+            .map_err(json_rejection)?;
+    deserializer.end().map_err(json_rejection)?;
+    unknown_fields(unknown)?;
+
+    #[derive(Deserialize)]
+    struct Names<'a> {
+        #[serde(borrow)]
+        theme: Option<Cow<'a, str>>,
+        #[serde(borrow)]
+        piece: Option<Cow<'a, str>>,
+    }
+
+    let names = serde_json::from_slice::<Names>(body).ok();
+    Ok(Strict {
+        value,
+        warnings: names
+            .iter()
+            .flat_map(|names| {
+                let theme = names.theme.as_ref().map(|name| ("theme", name));
+                let piece = names.piece.as_ref().map(|name| ("piece", name));
+                theme.into_iter().chain(piece)
+            })
+            .filter_map(|(field, name)| alias_warning(field, name))
+            .collect(),
+    })
+}
+
+fn gif<I>(warnings: Vec<String>, chunks: I) -> Response
+where
+    I: Iterator<Item = Bytes> + Send + 'static,
+{
+    let mut response = Response::builder()
+        .header(CONTENT_TYPE, "image/gif")
+        .header(&API_VERSION, VERSION);
+    for warning in warnings {
+        response = response.header(&WARNING, warning);
+    }
+    response
+        .body(Body::from_stream(stream::iter(
+            chunks.map(Ok::<_, Infallible>),
+        )))
+        .unwrap()
+}
+
+fn image(themes: &'static Themes, uri: Uri) -> Result<Response, Rejection> {
+    let req = from_query::<RequestParams>(&uri)?;
+    Ok(gif(req.warnings, Render::new_image(themes, req.value)))
+}
+
+fn game(themes: &'static Themes, headers: HeaderMap, body: Bytes) -> Result<Response, Rejection> {
+    let req = from_json::<RequestBody>(&headers, &body)?;
+    Ok(gif(req.warnings, Render::new_animation(themes, req.value)))
+}
+
+fn grid(themes: &'static Themes, headers: HeaderMap, body: Bytes) -> Result<Response, Rejection> {
+    let req = from_json::<GridRequest>(&headers, &body)?;
+    let render = GridRender::new_image(themes, req.value).map_err(Rejection::TooLarge)?;
+    Ok(gif(req.warnings, render))
+}
+
+fn games(themes: &'static Themes, headers: HeaderMap, body: Bytes) -> Result<Response, Rejection> {
+    let req = from_json::<GridAnimationRequest>(&headers, &body)?;
+    let render = GridRender::new_animation(themes, req.value).map_err(Rejection::TooLarge)?;
+    Ok(gif(req.warnings, render))
+}
+
+fn gallery(themes: &'static Themes, uri: Uri) -> Result<Response, Rejection> {
+    let req = from_query::<GalleryParams>(&uri)?;
+    let render = GridRender::new_gallery(themes, req.value).map_err(Rejection::TooLarge)?;
+    Ok(gif(req.warnings, render))
+}
+
+pub fn routes(themes: &'static Themes) -> Router {
+    Router::new()
+        .route(
+            "/image.gif",
+            get(move |uri| async move { image(themes, uri) }),
+        )
+        .route(
+            "/game.gif",
+            post(move |headers, body| async move { game(themes, headers, body) }),
+        )
+        .route(
+            "/grid.gif",
+            post(move |headers, body| async move { grid(themes, headers, body) }),
+        )
+        .route(
+            "/games.gif",
+            post(move |headers, body| async move { games(themes, headers, body) }),
+        )
+        .route(
+            "/gallery.gif",
+            get(move |uri| async move { gallery(themes, uri) }),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use lila_gif::api::{Title, VisibleSquares};
+    use shakmaty::Bitboard;
+
+    use super::*;
+
+    fn json_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers
+    }
+
+    fn rejection<T>(result: Result<Strict<T>, Rejection>) -> String {
+        match result {
+            Ok(_) => panic!("expected rejection"),
+            Err(Rejection::Data(message) | Rejection::Syntax(message)) => message,
+            Err(Rejection::UnsupportedMediaType) => "unsupported media type".to_owned(),
+            Err(Rejection::TooLarge(err)) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let uri: Uri = "/image.gif?fen=8/8/8/8/8/8/8/K6k&lastmove=e2e4"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rejection(from_query::<RequestParams>(&uri)),
+            "unknown field `lastmove`"
+        );
+
+        let body =
+            br#"{"frames": [{"lastMove": "e2e4", "clock": {"whte": 1}}], "white": {"ratng": 1}}"#;
+        assert_eq!(
+            rejection(from_json::<RequestBody>(&json_headers(), body)),
+            "unknown fields `frames.0.clock.whte`, `white.ratng`"
+        );
+
+        assert!(from_json::<RequestBody>(&HeaderMap::new(), br#"{"frames": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_visible_squares() {
+        let uri: Uri = "/image.gif?visible=18446744073709551615".parse().unwrap();
+        let req = from_query::<RequestParams>(&uri).expect("valid query");
+        assert_eq!(req.value.visible, Some(VisibleSquares(Bitboard::FULL)));
+
+        let body = br#"{"boards": [{"visible": "0xffff000000000000"}, {"visible": 65535}]}"#;
+        let req = from_json::<GridRequest>(&json_headers(), body).expect("valid body");
+        assert_eq!(
+            req.value.boards[0].visible,
+            Some(VisibleSquares(Bitboard(0xffff_0000_0000_0000)))
+        );
+        assert_eq!(
+            req.value.boards[1].visible,
+            Some(VisibleSquares(Bitboard(0xffff)))
+        );
+
+        for visible in ["18446462598732840960", "9007199254740993", "-1"] {
+            let body = format!(r#"{{"boards": [{{"visible": {visible}}}]}}"#);
+            assert!(
+                from_json::<GridRequest>(&json_headers(), body.as_bytes()).is_err(),
+                "{visible}"
+            );
+        }
+        let body = br#"{"boards": [{"visible": 9007199254740992}]}"#;
+        assert!(
+            rejection(from_json::<GridRequest>(&json_headers(), body))
+                .contains("pass them as a string")
+        );
+    }
+
+    #[test]
+    fn test_deprecated_aliases() {
+        let uri: Uri = "/image.gif?theme=maple".parse().unwrap();
+        let req = from_query::<RequestParams>(&uri).expect("valid query");
+        assert_eq!(req.warnings, ["theme maple is a deprecated alias of brown"]);
+
+        let req = from_json::<GridRequest>(&json_headers(), br#"{"theme": "brown", "boards": []}"#)
+            .expect("valid body");
+        assert!(req.warnings.is_empty());
+
+        let uri: Uri = "/gallery.gif?piece=kiwensuwi".parse().unwrap();
+        let req = from_query::<GalleryParams>(&uri).expect("valid query");
+        assert_eq!(
+            req.warnings,
+            ["piece kiwensuwi is a deprecated alias of kiwen-suwi"]
+        );
+
+        let req = from_json::<RequestBody>(
+            &json_headers(),
+            br#"{"theme": "wood", "piece": "kiwensuwi", "frames": []}"#,
+        )
+        .expect("valid body");
+        assert_eq!(
+            req.warnings,
+            [
+                "theme wood is a deprecated alias of brown",
+                "piece kiwensuwi is a deprecated alias of kiwen-suwi",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_player_fields() {
+        let uri: Uri = "/image.gif?white=Carlsen&whiteTitle=GM&black.rating=2800&blackTag=NO"
+            .parse()
+            .unwrap();
+        let mut req = from_query::<RequestParams>(&uri).expect("valid query");
+        assert_eq!(
+            req.warnings,
+            [
+                "whiteTitle is deprecated, use white.title",
+                "blackTag is deprecated, use black.tag",
+            ]
+        );
+        let white = req.value.white_player().expect("white");
+        assert_eq!(
+            (white.name.as_str(), white.title),
+            ("Carlsen", Some(Title::Gm))
+        );
+        let black = req.value.black_player().expect("black");
+        assert_eq!(black.rating, Some(2800));
+        assert_eq!(black.tag.as_deref(), Some("NO"));
+
+        let uri: Uri = "/image.gif?white.titel=GM".parse().unwrap();
+        assert_eq!(
+            rejection(from_query::<RequestParams>(&uri)),
+            "unknown field `white.titel`"
+        );
+    }
+}
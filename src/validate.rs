@@ -0,0 +1,221 @@
+//! Structural validation of emitted GIF streams.
+//!
+//! `Render` and `GridRender` stitch a GIF together from partial blocks, so
+//! a mistake in one code path can produce a stream that lenient decoders
+//! accept but others reject. [`validate`] parses a complete stream block by
+//! block and checks the things a strict decoder would.
+//!
+//! Only the stream itself is checked, not what the encoder meant to write.
+//! A logical screen whose size was truncated to 16 bits is consistent with
+//! images truncated the same way, so it passes. Renderers reject such sizes
+//! up front instead.
+
+use std::{error::Error, fmt};
+
+use gift::{Decoder, block::Block};
+
+/// Facts about a valid GIF stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GifSummary {
+    pub width: u16,
+    pub height: u16,
+    /// Number of images, including partial frames.
+    pub images: usize,
+    /// Sum of all frame delays in centiseconds.
+    pub duration_cs: u32,
+}
+
+/// A problem found in a GIF stream. `block` is the index of the offending
+/// block, counting from the header.
+#[derive(Debug)]
+pub enum InvalidGif {
+    Decode(gift::Error),
+    MissingTrailer,
+    TrailingData {
+        bytes: usize,
+    },
+    GraphicControlWithoutImage {
+        block: usize,
+    },
+    EmptyImage {
+        block: usize,
+    },
+    ImageOutOfBounds {
+        block: usize,
+    },
+    ImageDataWithoutDescriptor {
+        block: usize,
+    },
+    MissingColorTable {
+        block: usize,
+    },
+    ImageDataSize {
+        block: usize,
+        expected: usize,
+        actual: usize,
+    },
+    ColorIndexOutOfRange {
+        block: usize,
+        index: u8,
+        colors: usize,
+    },
+    TransparentIndexOutOfRange {
+        block: usize,
+        index: u8,
+        colors: usize,
+    },
+}
+
+impl fmt::Display for InvalidGif {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvalidGif::Decode(ref err) => write!(f, "decode error: {err}"),
+            InvalidGif::MissingTrailer => f.write_str("stream ends without trailer"),
+            InvalidGif::TrailingData { bytes } => write!(f, "{bytes} bytes after trailer"),
+            InvalidGif::GraphicControlWithoutImage { block } => {
+                write!(f, "block {block}: graphic control not followed by an image")
+            }
+            InvalidGif::EmptyImage { block } => write!(f, "block {block}: image has no pixels"),
+            InvalidGif::ImageOutOfBounds { block } => {
+                write!(f, "block {block}: image exceeds logical screen")
+            }
+            InvalidGif::ImageDataWithoutDescriptor { block } => {
+                write!(f, "block {block}: image data without image descriptor")
+            }
+            InvalidGif::MissingColorTable { block } => {
+                write!(
+                    f,
+                    "block {block}: image without global or local color table"
+                )
+            }
+            InvalidGif::ImageDataSize {
+                block,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "block {block}: image data has {actual} pixels, expected {expected}"
+            ),
+            InvalidGif::ColorIndexOutOfRange {
+                block,
+                index,
+                colors,
+            } => write!(
+                f,
+                "block {block}: color index {index} outside color table of {colors}"
+            ),
+            InvalidGif::TransparentIndexOutOfRange {
+                block,
+                index,
+                colors,
+            } => write!(
+                f,
+                "block {block}: transparent index {index} outside color table of {colors}"
+            ),
+        }
+    }
+}
+
+impl Error for InvalidGif {}
+
+/// Checks block ordering, image bounds against the logical screen, and
+/// color and transparent index usage of a complete GIF stream.
+pub fn validate(gif: &[u8]) -> Result<GifSummary, InvalidGif> {
+    let mut reader = gif;
+    let mut summary = GifSummary {
+        width: 0,
+        height: 0,
+        images: 0,
+        duration_cs: 0,
+    };
+    let mut global_colors = 0;
+    let mut local_colors = None;
+    let mut image = None;
+    let mut transparent = None;
+    let mut pending_control = None;
+    let mut trailer = false;
+
+    for (index, block) in Decoder::new(&mut reader).into_blocks().enumerate() {
+        match block.map_err(InvalidGif::Decode)? {
+            Block::LogicalScreenDesc(desc) => {
+                summary.width = desc.screen_width();
+                summary.height = desc.screen_height();
+            }
+            Block::GlobalColorTable(table) => global_colors = table.len(),
+            Block::GraphicControl(ctrl) => {
+                if let Some(block) = pending_control {
+                    return Err(InvalidGif::GraphicControlWithoutImage { block });
+                }
+                pending_control = Some(index);
+                transparent = ctrl.transparent_color();
+                summary.duration_cs += u32::from(ctrl.delay_time_cs());
+            }
+            Block::PlainText(_) => {
+                pending_control = None;
+                transparent = None;
+            }
+            Block::ImageDesc(desc) => {
+                if desc.width() == 0 || desc.height() == 0 {
+                    return Err(InvalidGif::EmptyImage { block: index });
+                }
+                if u32::from(desc.left()) + u32::from(desc.width()) > u32::from(summary.width)
+                    || u32::from(desc.top()) + u32::from(desc.height()) > u32::from(summary.height)
+                {
+                    return Err(InvalidGif::ImageOutOfBounds { block: index });
+                }
+                pending_control = None;
+                local_colors = None;
+                image = Some((index, desc.image_sz()));
+            }
+            Block::LocalColorTable(table) => local_colors = Some(table.len()),
+            Block::ImageData(data) => {
+                let (block, expected) = image
+                    .take()
+                    .ok_or(InvalidGif::ImageDataWithoutDescriptor { block: index })?;
+                let colors = local_colors.unwrap_or(global_colors);
+                if colors == 0 {
+                    return Err(InvalidGif::MissingColorTable { block });
+                }
+                if data.data().len() != expected {
+                    return Err(InvalidGif::ImageDataSize {
+                        block,
+                        expected,
+                        actual: data.data().len(),
+                    });
+                }
+                if let Some(index) = transparent.take().filter(|&t| usize::from(t) >= colors) {
+                    return Err(InvalidGif::TransparentIndexOutOfRange {
+                        block,
+                        index,
+                        colors,
+                    });
+                }
+                if let Some(&index) = data.data().iter().find(|&&i| usize::from(i) >= colors) {
+                    return Err(InvalidGif::ColorIndexOutOfRange {
+                        block,
+                        index,
+                        colors,
+                    });
+                }
+                summary.images += 1;
+            }
+            Block::Trailer(_) => {
+                if let Some(block) = pending_control {
+                    return Err(InvalidGif::GraphicControlWithoutImage { block });
+                }
+                trailer = true;
+            }
+            Block::Header(_) | Block::Comment(_) | Block::Application(_) | Block::Unknown(_) => (),
+        }
+    }
+
+    if !trailer {
+        return Err(InvalidGif::MissingTrailer);
+    }
+    if !reader.is_empty() {
+        return Err(InvalidGif::TrailingData {
+            bytes: reader.len(),
+        });
+    }
+    Ok(summary)
+}
@@ -0,0 +1,400 @@
+//! Golden image tests.
+//!
+//! Renders a corpus of requests and compares the decoded pixel indices of
+//! every frame against the references in `tests/golden/`. On mismatch, the
+//! actual output and an image marking the differing pixels are written to
+//! the target directory. Every output must also pass the stream validator.
+//!
+//! After an intended change in rendering, regenerate the references with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review them before
+//! committing.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use gift::{
+    Decoder, Encoder,
+    block::{self, Frame, Preamble},
+};
+use lila_gif::{
+    Render, Themes,
+    api::{RequestBody, RequestParams},
+    assets::{BoardTheme, PieceSet},
+    validate::validate,
+};
+use serde_json::json;
+
+enum Request {
+    Image(RequestParams),
+    Game(RequestBody),
+}
+
+struct Case {
+    name: String,
+    request: Request,
+}
+
+impl Case {
+    fn image(name: impl Into<String>, params: serde_json::Value) -> Case {
+        Case {
+            name: name.into(),
+            request: Request::Image(serde_json::from_value(params).expect("image params")),
+        }
+    }
+
+    fn game(name: impl Into<String>, body: serde_json::Value) -> Case {
+        Case {
+            name: name.into(),
+            request: Request::Game(serde_json::from_value(body).expect("game body")),
+        }
+    }
+
+    fn render(self, themes: &Themes) -> Vec<u8> {
+        match self.request {
+            Request::Image(params) => Render::new_image(themes, params).flatten().collect(),
+            Request::Game(body) => Render::new_animation(themes, body).flatten().collect(),
+        }
+    }
+}
+
+fn corpus() -> Vec<Case> {
+    let mut cases = vec![Case {
+        name: "example".to_owned(),
+        request: Request::Game(RequestBody::example()),
+    }];
+
+    // Every piece of both colors, a check and a last move on each sprite
+    // sheet.
+    for board in BoardTheme::ALL {
+        for piece in PieceSet::ALL {
+            let mut params: RequestParams = serde_json::from_value(json!({
+                "fen": "4k3/1q1r1b1n/4p3/8/8/2P5/N1B1R1Q1/4K3 b - - 0 1",
+                "lastMove": "d1e2",
+                "check": true,
+            }))
+            .expect("image params");
+            params.theme = board;
+            params.piece = piece;
+            cases.push(Case {
+                name: format!("board-{board:?}-{piece:?}").to_lowercase(),
+                request: Request::Image(params),
+            });
+        }
+    }
+
+    for orientation in ["white", "black"] {
+        cases.push(Case::image(
+            format!("orientation-{orientation}"),
+            json!({
+                "fen": "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+                "lastMove": "b8c6",
+                "orientation": orientation,
+            }),
+        ));
+    }
+
+    // Capture targets on last move, highlighted and plain squares.
+    cases.push(Case::image(
+        "captures",
+        json!({
+            "fen": "4k3/8/8/3p3n/p7/8/8/3QK3 w - - 0 1",
+            "lastMove": "d7d5",
+            "selected": "d1",
+            "highlights": { "a4": "red", "h5": "blue" },
+        }),
+    ));
+
+    // Destinations on hidden pieces are plain dots.
+    cases.push(Case::image(
+        "captures-blindfold",
+        json!({
+            "fen": "4k3/8/8/3p3n/p7/8/8/3QK3 w - - 0 1",
+            "selected": "d1",
+            "blindfold": "black",
+        }),
+    ));
+
+    // Ghosts on an empty and on an occupied square, where the piece must
+    // stay visible.
+    cases.push(Case::game(
+        "ghosts",
+        json!({
+            "delay": 100,
+            "frames": [
+                {
+                    "fen": "4k3/8/8/3p3n/p7/8/8/3QK3 w - - 0 1",
+                    "premove": "d1d4",
+                    "ghost": "Qd4",
+                },
+                {
+                    "fen": "4k3/8/8/3p3n/p7/8/8/3QK3 w - - 0 1",
+                    "premove": "d1h5",
+                    "ghost": "Qh5",
+                },
+            ],
+        }),
+    ));
+
+    cases.push(Case::image(
+        "bars",
+        json!({
+            "fen": "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "white": "Magnus Carlsen",
+            "whiteTitle": "GM",
+            "whiteRating": 2863,
+            "whiteRatingDiff": 7,
+            "whiteTag": "NO",
+            "black": "lichess AI level 8",
+            "blackTitle": "BOT",
+            "blackRating": 3000,
+            "blackRatingDiff": -7,
+        }),
+    ));
+
+    cases.push(Case::game(
+        "clocks",
+        json!({
+            "white": "Carlsen",
+            "black": "Nakamura",
+            "delay": 100,
+            "frames": [
+                {
+                    "fen": "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                    "clock": { "white": 18000, "black": 18000 },
+                },
+                {
+                    "fen": "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+                    "lastMove": "e2e4",
+                    "clock": { "white": 17850, "black": 18000 },
+                },
+                {
+                    "fen": "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+                    "lastMove": "e7e5",
+                    "clock": { "white": 17850, "black": 950 },
+                },
+            ],
+        }),
+    ));
+
+    let frames: Vec<_> = ["!", "!!", "?", "??", "!?", "?!", "□", "⨀"]
+        .into_iter()
+        .zip([
+            "e7e5", "f8c5", "g8f6", "f7f6", "b8c6", "d7d6", "e8e7", "h7h6",
+        ])
+        .map(|(glyph, last_move)| {
+            json!({
+                "fen": "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+                "lastMove": last_move,
+                "glyph": glyph,
+            })
+        })
+        .collect();
+    cases.push(Case::game(
+        "glyphs",
+        json!({
+            "delay": 100,
+            "frames": frames,
+        }),
+    ));
+
+    cases
+}
+
+struct Decoded {
+    preamble: Preamble,
+    frames: Vec<Frame>,
+}
+
+fn decode(gif: &[u8]) -> Decoded {
+    let mut frames = Decoder::new(gif).into_frames();
+    let preamble = frames
+        .preamble()
+        .expect("decode preamble")
+        .expect("preamble");
+    Decoded {
+        preamble,
+        frames: frames.collect::<Result<_, _>>().expect("decode frames"),
+    }
+}
+
+/// Describes the first difference between two decoded outputs, and the
+/// index of the frame that differs, if any.
+fn compare(expected: &Decoded, actual: &Decoded) -> Option<(String, Option<usize>)> {
+    if (
+        expected.preamble.screen_width(),
+        expected.preamble.screen_height(),
+    ) != (
+        actual.preamble.screen_width(),
+        actual.preamble.screen_height(),
+    ) {
+        return Some((
+            format!(
+                "screen size {}x{} != {}x{}",
+                actual.preamble.screen_width(),
+                actual.preamble.screen_height(),
+                expected.preamble.screen_width(),
+                expected.preamble.screen_height()
+            ),
+            None,
+        ));
+    }
+    if expected.preamble.global_color_table != actual.preamble.global_color_table {
+        return Some(("global color table differs".to_owned(), None));
+    }
+    if expected.preamble.loop_count_ext != actual.preamble.loop_count_ext {
+        return Some(("loop count differs".to_owned(), None));
+    }
+    for (i, (e, a)) in expected.frames.iter().zip(&actual.frames).enumerate() {
+        let delay = |f: &Frame| f.graphic_control_ext.map(|ctrl| ctrl.delay_time_cs());
+        let reason = if e.region() != a.region() {
+            "region"
+        } else if delay(e) != delay(a) {
+            "delay"
+        } else if e.disposal_method() != a.disposal_method() {
+            "disposal method"
+        } else if e.transparent_color() != a.transparent_color() {
+            "transparent color"
+        } else if e.local_color_table != a.local_color_table {
+            "local color table"
+        } else if e.image_data.data() != a.image_data.data() {
+            "pixel indices"
+        } else {
+            continue;
+        };
+        return Some((format!("frame {i}: {reason} differs"), Some(i)));
+    }
+    if expected.frames.len() != actual.frames.len() {
+        return Some((
+            format!(
+                "{} frames != {} frames",
+                actual.frames.len(),
+                expected.frames.len()
+            ),
+            Some(expected.frames.len().min(actual.frames.len())),
+        ));
+    }
+    None
+}
+
+/// Composites frames up to and including `until` onto a canvas of color
+/// indices.
+fn composite(decoded: &Decoded, until: usize) -> Vec<u8> {
+    let width = usize::from(decoded.preamble.screen_width());
+    let height = usize::from(decoded.preamble.screen_height());
+    let mut canvas = vec![0; width * height];
+    for frame in decoded.frames.iter().take(until + 1) {
+        let (left, top) = (usize::from(frame.left()), usize::from(frame.top()));
+        let frame_width = usize::from(frame.width());
+        for (i, &index) in frame.image_data.data().iter().enumerate() {
+            let (x, y) = (left + i % frame_width, top + i / frame_width);
+            if Some(index) != frame.transparent_color() && x < width && y < height {
+                canvas[y * width + x] = index;
+            }
+        }
+    }
+    canvas
+}
+
+/// Writes a two color image marking pixels that differ after compositing
+/// frames up to `until`.
+fn write_diff(path: &Path, expected: &Decoded, actual: &Decoded, until: usize) {
+    let width = actual.preamble.screen_width();
+    let height = actual.preamble.screen_height();
+    let diff: Vec<u8> = composite(expected, until)
+        .into_iter()
+        .zip(composite(actual, until))
+        .map(|(e, a)| u8::from(e != a))
+        .collect();
+
+    let mut blocks = Encoder::new(fs::File::create(path).expect("create diff")).into_block_enc();
+    blocks.encode(block::Header::default()).expect("enc header");
+    blocks
+        .encode(
+            block::LogicalScreenDesc::default()
+                .with_screen_width(width)
+                .with_screen_height(height)
+                .with_color_table_config(block::ColorTableConfig::new(
+                    block::ColorTableExistence::Present,
+                    block::ColorTableOrdering::NotSorted,
+                    2,
+                )),
+        )
+        .expect("enc logical screen desc");
+    blocks
+        .encode(block::GlobalColorTable::with_colors(&[
+            0x40, 0x40, 0x40, 0xff, 0x00, 0x00,
+        ]))
+        .expect("enc global color table");
+    blocks
+        .encode(
+            block::ImageDesc::default()
+                .with_width(width)
+                .with_height(height),
+        )
+        .expect("enc image desc");
+    let mut image_data = block::ImageData::new(diff.len());
+    image_data.data_mut().extend_from_slice(&diff);
+    blocks.encode(image_data).expect("enc image data");
+    blocks
+        .encode(block::Trailer::default())
+        .expect("enc trailer");
+}
+
+#[test]
+fn golden() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let references = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let failures_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&references).expect("create references dir");
+    fs::create_dir_all(&failures_dir).expect("create failures dir");
+
+    let themes = Themes::new();
+    let mut failures = Vec::new();
+
+    for case in corpus() {
+        let name = case.name.clone();
+        let reference = references.join(format!("{name}.gif"));
+        let gif = case.render(&themes);
+        if let Err(err) = validate(&gif) {
+            failures.push(format!("{name}: {err}"));
+            continue;
+        }
+        let actual = decode(&gif);
+
+        if update {
+            fs::write(&reference, &gif).expect("write reference");
+            continue;
+        }
+
+        let Ok(expected) = fs::read(&reference) else {
+            failures.push(format!(
+                "{name}: missing reference, run with UPDATE_GOLDEN=1"
+            ));
+            continue;
+        };
+        let expected = decode(&expected);
+
+        if let Some((reason, frame)) = compare(&expected, &actual) {
+            fs::write(failures_dir.join(format!("{name}.actual.gif")), &gif).expect("write actual");
+            if let Some(frame) = frame {
+                write_diff(
+                    &failures_dir.join(format!("{name}.diff.gif")),
+                    &expected,
+                    &actual,
+                    frame,
+                );
+            }
+            failures.push(format!("{name}: {reason}"));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} golden image(s) differ, see {}:\n{}",
+        failures.len(),
+        failures_dir.display(),
+        failures.join("\n")
+    );
+}
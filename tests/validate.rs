@@ -0,0 +1,305 @@
+//! Runs the stream validator over rendering paths that emit partial blocks,
+//! and checks that it rejects malformed streams.
+
+use std::sync::LazyLock;
+
+use gift::{Decoder, block::Block};
+use lila_gif::{
+    GridRender, Render, Themes,
+    api::{GalleryParams, GridAnimationRequest, GridRequest, RequestBody, RequestParams},
+    validate::{InvalidGif, validate},
+};
+use serde_json::json;
+
+static THEMES: LazyLock<Themes> = LazyLock::new(Themes::new);
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const E4: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+const E5: &str = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+const TAKES: &str = "rnbqkbnr/ppp2ppp/8/3pp3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3";
+const EXD5: &str = "rnbqkbnr/ppp2ppp/8/3Pp3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3";
+
+fn frames() -> serde_json::Value {
+    json!([
+        { "fen": START, "clock": { "white": 18000, "black": 18000 } },
+        { "fen": E4, "lastMove": "e2e4", "clock": { "white": 17900, "black": 18000 } },
+        { "fen": E5, "lastMove": "e7e5", "clock": { "white": 17900, "black": 17950 } },
+        { "fen": TAKES, "lastMove": "d7d5", "clock": { "white": 17900, "black": 17950 } },
+        { "fen": EXD5, "lastMove": "e4d5", "clock": { "black": 17000 } },
+    ])
+}
+
+fn game(body: serde_json::Value) -> Vec<u8> {
+    let body: RequestBody = serde_json::from_value(body).expect("game body");
+    Render::new_animation(&THEMES, body).flatten().collect()
+}
+
+fn blocks(mut gif: &[u8]) -> Vec<Block> {
+    Decoder::new(&mut gif)
+        .into_blocks()
+        .collect::<Result<_, _>>()
+        .expect("decode")
+}
+
+fn image_indices(blocks: &[Block]) -> Vec<usize> {
+    blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| matches!(block, Block::ImageDesc(_)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[test]
+fn valid_renders() {
+    let themes = &*THEMES;
+    let mut outputs = Vec::new();
+
+    let params: RequestParams = serde_json::from_value(json!({
+        "fen": E5,
+        "white": "Carlsen",
+        "blackRating": 2800,
+        "coordinates": "outside",
+    }))
+    .expect("image params");
+    outputs.push((
+        "image",
+        Render::new_image(themes, params).flatten().collect(),
+    ));
+
+    for (name, options) in [
+        ("clocks", json!({ "white": "Carlsen", "black": "Nakamura" })),
+        ("material", json!({ "material": true })),
+        ("caption", json!({ "caption": true, "coordinates": "all" })),
+        (
+            "result",
+            json!({ "result": { "outcome": "1-0", "status": "resign" } }),
+        ),
+        ("trailer", json!({ "trailer": "repeat", "plays": 2 })),
+        (
+            "region",
+            json!({ "region": "c3:f6", "coordinates": "outside" }),
+        ),
+        (
+            "blindfold",
+            json!({ "blindfold": "discs", "orientation": "black" }),
+        ),
+    ] {
+        let mut body = options;
+        body["frames"] = frames();
+        outputs.push((name, game(body)));
+    }
+
+    let grid: GridRequest = serde_json::from_value(json!({
+        "columns": 2,
+        "coordinates": "outside",
+        "boards": [
+            { "fen": E4, "lastMove": "e2e4", "caption": "Board 1" },
+            { "fen": E5, "orientation": "black", "fog": "white" },
+            { "fen": TAKES, "selected": "e4", "ghost": "Pd5" },
+        ],
+    }))
+    .expect("grid request");
+    outputs.push((
+        "grid",
+        GridRender::new_image(themes, grid)
+            .expect("grid size")
+            .flatten()
+            .collect(),
+    ));
+
+    let games: GridAnimationRequest = serde_json::from_value(json!({
+        "columns": 2,
+        "games": [
+            { "caption": "Board 1", "frames": frames() },
+            { "orientation": "black", "frames": [{ "fen": START }, { "fen": E4 }] },
+        ],
+    }))
+    .expect("games request");
+    outputs.push((
+        "games",
+        GridRender::new_animation(themes, games)
+            .expect("grid size")
+            .flatten()
+            .collect(),
+    ));
+
+    // Cells with other palettes are separate images with local color tables.
+    for (name, params) in [
+        ("gallery-pieces", json!({ "theme": "green", "columns": 8 })),
+        (
+            "gallery-themes",
+            json!({ "piece": "merida", "fen": E4, "lastMove": "e2e4" }),
+        ),
+    ] {
+        let params: GalleryParams = serde_json::from_value(params).expect("gallery params");
+        outputs.push((
+            name,
+            GridRender::new_gallery(themes, params)
+                .expect("grid size")
+                .flatten()
+                .collect(),
+        ));
+    }
+
+    for (name, gif) in outputs {
+        if let Err(err) = validate(&gif) {
+            panic!("{name}: {err}");
+        }
+    }
+}
+
+#[test]
+fn long_names_with_material() {
+    let themes = &*THEMES;
+    for name in ["Magnus Carlsen Magnus Carlsen!", &"W".repeat(100)] {
+        let player = json!({
+            "name": name,
+            "title": "GM",
+            "rating": 2863,
+            "ratingDiff": -7,
+            "tag": "NO",
+        });
+
+        let params: RequestParams = serde_json::from_value(json!({
+            "fen": TAKES,
+            "white": player,
+            "black": player,
+            "material": true,
+        }))
+        .expect("image params");
+        let image: Vec<u8> = Render::new_image(themes, params).flatten().collect();
+        validate(&image).expect("valid image");
+
+        let gif = game(json!({
+            "white": player,
+            "black": player,
+            "material": true,
+            "frames": frames(),
+        }));
+        validate(&gif).expect("valid game");
+    }
+}
+
+#[test]
+fn rejects_oversized_grids() {
+    let boards: Vec<_> = (0..64)
+        .map(|i| json!({ "fen": E4, "caption": format!("Board {i}") }))
+        .collect();
+
+    // Would wrap around in the 16 bit logical screen height.
+    let tall: GridRequest = serde_json::from_value(json!({
+        "columns": 1,
+        "spacing": 200,
+        "coordinates": "outside",
+        "boards": boards,
+    }))
+    .expect("grid request");
+    let err = GridRender::new_image(&THEMES, tall)
+        .err()
+        .expect("too large");
+    assert!(err.height > usize::from(u16::MAX));
+
+    let square: GridRequest = serde_json::from_value(json!({
+        "coordinates": "outside",
+        "boards": boards,
+    }))
+    .expect("grid request");
+    assert!(GridRender::new_image(&THEMES, square).is_ok());
+
+    let games: GridAnimationRequest = serde_json::from_value(json!({
+        "columns": 1,
+        "spacing": 200,
+        "coordinates": "outside",
+        "games": (0..64)
+            .map(|_| json!({ "frames": frames() }))
+            .collect::<Vec<_>>(),
+    }))
+    .expect("games request");
+    assert!(GridRender::new_animation(&THEMES, games).is_err());
+}
+
+#[test]
+fn games_step_in_lockstep() {
+    let games: GridAnimationRequest = serde_json::from_value(json!({
+        "delay": 80,
+        "trailer": "none",
+        "games": [
+            { "frames": frames() },
+            { "orientation": "black", "frames": frames() },
+            { "frames": frames() },
+            { "frames": [{ "fen": START }, { "fen": E4, "lastMove": "e2e4" }] },
+        ],
+    }))
+    .expect("games request");
+    let gif: Vec<u8> = GridRender::new_animation(&THEMES, games)
+        .expect("grid size")
+        .flatten()
+        .collect();
+    validate(&gif).expect("valid games");
+
+    // The first image is the initial canvas, then one image per step.
+    let blocks = blocks(&gif);
+    let images = image_indices(&blocks);
+    assert_eq!(images.len(), 1 + 4);
+    for &index in &images[1..] {
+        match &blocks[index - 1] {
+            Block::GraphicControl(ctrl) => assert_eq!(ctrl.delay_time_cs(), 80),
+            _ => panic!("block {index}: image without graphic control"),
+        }
+    }
+}
+
+#[test]
+fn gallery_is_a_single_image() {
+    let params: GalleryParams =
+        serde_json::from_value(json!({ "theme": "green" })).expect("gallery params");
+    let gif: Vec<u8> = GridRender::new_gallery(&THEMES, params)
+        .expect("grid size")
+        .flatten()
+        .collect();
+
+    // Cells with different palettes share one global color table, so
+    // browsers draw the whole gallery at once.
+    let blocks = blocks(&gif);
+    assert_eq!(image_indices(&blocks).len(), 1);
+    assert!(
+        !blocks
+            .iter()
+            .any(|block| matches!(block, Block::LocalColorTable(_)))
+    );
+    assert_eq!(validate(&gif).expect("valid gallery").duration_cs, 0);
+}
+
+#[test]
+fn summary() {
+    let summary = validate(&game(
+        json!({ "delay": 100, "trailer": "none", "frames": frames() }),
+    ))
+    .expect("valid game");
+    assert_eq!((summary.width, summary.height), (720, 840));
+    assert_eq!(summary.duration_cs, 500);
+}
+
+#[test]
+fn rejects_malformed_streams() {
+    let gif = game(json!({ "frames": frames() }));
+
+    // Graphic control extension (introducer, label, size 4, flags, delay,
+    // transparent index, terminator) right before the trailer.
+    let mut dangling = gif[..gif.len() - 1].to_vec();
+    dangling.extend_from_slice(&[0x21, 0xf9, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x3b]);
+    assert!(matches!(
+        validate(&dangling),
+        Err(InvalidGif::GraphicControlWithoutImage { .. })
+    ));
+
+    let mut trailing = gif.clone();
+    trailing.push(0);
+    assert!(matches!(
+        validate(&trailing),
+        Err(InvalidGif::TrailingData { bytes: 1 })
+    ));
+
+    assert!(validate(&gif[..gif.len() - 1]).is_err());
+}
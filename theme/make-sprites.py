@@ -10,14 +10,34 @@ from PIL import Image, ImageColor, ImageDraw
 
 
 SQUARE_SIZE = 90
+BAR_PIECE_SIZE = 30
+GHOST_ROW = 8
+BLINDFOLD_ROW = 14
+GRADIENT_ROW = 16
 
 TRANSPARENCY = "#ffffff00"
 HIGHLIGHT = "#9bc70069"
+PREMOVE_HIGHLIGHT = "#141e5580"
+FOG = "#262421c0"
+GHOST_OPACITY = 0.5
+DISC_COLOR = "#9e9e9e"
+DISC_BORDER_COLOR = "#5c5c5c"
 DARK_BACKGROUND = "#262421"
 TEXT_COLOR = "#bababa"
 TITLE_COLOR = "#bf811d"
 BOT_COLOR = "#b72fc6"
 GLYPH_TEXT_COLOR = "#ffffff"
+RATING_GAIN_COLOR = "#629924"
+RATING_LOSS_COLOR = "#cc3333"
+
+SQUARE_HIGHLIGHTS = [
+    "#15781b80", # green
+    "#88202080", # red
+    "#00308880", # blue
+    "#e68f0080", # yellow
+    PREMOVE_HIGHLIGHT,
+    FOG,
+]
 
 BOARD_THEMES = {
     "blue":   ("#dee3e6", "#8ca2ad"),
@@ -41,21 +61,23 @@ NONTHEME_COLORS = [
     (GLYPH_TEXT_COLOR, "#56b4e9"), # ?!
     (GLYPH_TEXT_COLOR, "#a04048"), # □
     (GLYPH_TEXT_COLOR, "#9171f2"), # ⨀
+    (RATING_GAIN_COLOR, DARK_BACKGROUND), # +8
+    (RATING_LOSS_COLOR, DARK_BACKGROUND), # -8
 ]
 
 
-def resvg(path):
+def resvg(path, width=SQUARE_SIZE):
     res = subprocess.run(
-        ["resvg", path, "-c", "-w", "90"],
+        ["resvg", path, "-c", "-w", str(width)],
         stdout=subprocess.PIPE,
     )
 
     return Image.open(io.BytesIO(res.stdout), formats=["PNG"])
 
 
-def resvg_pieces(piece_set):
+def resvg_pieces(piece_set, width=SQUARE_SIZE):
     print(f"Preparing {piece_set} pieces...")
-    return {f"{color}{piece}": resvg(f"piece/{piece_set}/{color}{piece}.svg") for color in "wb" for piece in "PNBRQK"}
+    return {f"{color}{piece}": resvg(f"piece/{piece_set}/{color}{piece}.svg", width) for color in "wb" for piece in "PNBRQK"}
 
 
 def blend(bottom, top):
@@ -65,10 +87,20 @@ def blend(bottom, top):
     return f"#{r:02x}{g:02x}{b:02x}"
 
 
-def make_sprite(light, dark, pieces, check_gradient):
+def make_disc(scale=4):
+    size = SQUARE_SIZE * scale
+    disc = Image.new("RGBA", (size, size), TRANSPARENCY)
+    draw = ImageDraw.Draw(disc)
+    margin = size * 0.15
+    draw.ellipse((margin, margin, size - margin, size - margin), fill=DISC_COLOR, outline=DISC_BORDER_COLOR, width=3 * scale)
+    return disc.resize((SQUARE_SIZE, SQUARE_SIZE), Image.LANCZOS)
+
+
+def make_sprite(light, dark, pieces, bar_pieces, check_gradient, disc):
     gradients = [(light, dark), (blend(light, HIGHLIGHT), blend(dark, HIGHLIGHT))] + NONTHEME_COLORS
+    gradients += [(blend(light, color), blend(dark, color)) for color in SQUARE_HIGHLIGHTS]
 
-    image = Image.new("RGB", (8 * SQUARE_SIZE, (7 + len(gradients)) * SQUARE_SIZE))
+    image = Image.new("RGB", (8 * SQUARE_SIZE, (GRADIENT_ROW + len(gradients)) * SQUARE_SIZE))
     draw = ImageDraw.Draw(image, "RGBA")
 
     for x in range(8):
@@ -88,6 +120,38 @@ def make_sprite(light, dark, pieces, check_gradient):
             piece = pieces[f"{color}{piece}"]
             image.paste(piece, pos, piece)
 
+    # Semi-transparent ghost pieces
+    for x in range(8):
+        fill = light if x % 2 == 0 else dark
+        rect = (x * SQUARE_SIZE, GHOST_ROW * SQUARE_SIZE, (x + 1) * SQUARE_SIZE - 1, GRADIENT_ROW * SQUARE_SIZE - 1)
+        draw.rectangle(rect, fill=blend(fill, HIGHLIGHT) if x in [2, 3, 6, 7] else fill)
+
+        color = "b" if x < 4 else "w"
+        for y, piece in enumerate("PNBRQK"):
+            piece = pieces[f"{color}{piece}"]
+            mask = piece.getchannel("A").point(lambda a: int(a * GHOST_OPACITY))
+            image.paste(piece, (x * SQUARE_SIZE, (GHOST_ROW + y) * SQUARE_SIZE), mask)
+
+    # Hidden pieces (only check) and neutral discs for blindfold modes
+    for y in range(2):
+        for x in range(8):
+            fill = light if x % 2 == 0 else dark
+            pos = (x * SQUARE_SIZE, (BLINDFOLD_ROW + y) * SQUARE_SIZE)
+            rect = (pos[0], pos[1], pos[0] + SQUARE_SIZE - 1, pos[1] + SQUARE_SIZE - 1)
+            draw.rectangle(rect, fill=blend(fill, HIGHLIGHT) if x in [2, 3, 6, 7] else fill)
+
+            if x >= 4:
+                image.paste(check_gradient, pos, check_gradient)
+
+            if y == 1:
+                image.paste(disc, pos, disc)
+
+    # Small pieces for the material difference in player bars
+    draw.rectangle((0, 7 * SQUARE_SIZE, 8 * SQUARE_SIZE - 1, 8 * SQUARE_SIZE - 1), fill=DARK_BACKGROUND)
+    for i, key in enumerate(f"{color}{piece}" for color in "wb" for piece in "PNBRQ"):
+        piece = bar_pieces[key]
+        image.paste(piece, (i * BAR_PIECE_SIZE, 7 * SQUARE_SIZE), piece)
+
     image = image.convert("RGBA")
     draw = ImageDraw.Draw(image, "RGBA")
 
@@ -98,21 +162,23 @@ def make_sprite(light, dark, pieces, check_gradient):
         t = np.linspace(0, 1, 8 * SQUARE_SIZE)[None, :, None]
         line = (left_color * (1 - t) + right_color * t).astype(np.uint8)
         rectangle = Image.fromarray(np.broadcast_to(line, (SQUARE_SIZE, 8 * SQUARE_SIZE, 4)), "RGBA")
-        image.paste(rectangle, (0, (7 + i) * SQUARE_SIZE))
+        image.paste(rectangle, (0, (GRADIENT_ROW + i) * SQUARE_SIZE))
 
     return image.quantize(255, dither=0)
 
 def main():
     check_gradient = resvg("check-gradient.svg")
+    disc = make_disc()
 
     piece_dirs = [ os.path.basename(f.path) for f in os.scandir("piece") if f.is_dir() ]
     piece_sets = {piece_set: resvg_pieces(piece_set) for piece_set in piece_dirs}
+    bar_piece_sets = {piece_set: resvg_pieces(piece_set, BAR_PIECE_SIZE) for piece_set in piece_dirs}
 
     for board_theme, (light, dark) in BOARD_THEMES.items():
         print(f"Generating sprites for {board_theme}...")
         for piece_set, pieces in piece_sets.items():
-            image = make_sprite(light=light, dark=dark, pieces=pieces, check_gradient=check_gradient)
-            image.save(f"sprites/{board_theme}-{piece_set}.gif", optimize=True, interlace=False, transparency=image.getpixel((0, SQUARE_SIZE * 9)))
+            image = make_sprite(light=light, dark=dark, pieces=pieces, bar_pieces=bar_piece_sets[piece_set], check_gradient=check_gradient, disc=disc)
+            image.save(f"sprites/{board_theme}-{piece_set}.gif", optimize=True, interlace=False, transparency=image.getpixel((0, SQUARE_SIZE * (GRADIENT_ROW + 2))))
 
     rust_code_updates(piece_dirs)
 
@@ -130,17 +196,20 @@ def rust_code_updates(piece_dirs):
         if piece_set == "cburnett":
             rust_enum += f"    #[default]\n"
         elif "-" in piece_set:
-            rust_enum += f"    #[serde(rename = \"{piece_set}\")]\n"
+            rust_enum += f"    #[strum(to_string = \"{piece_set}\")]\n"
         rust_enum += f"    {to_pascal_case(piece_set)},\n"
     rust_enum += "}"
     print(rust_enum)
 
     print("#" * 80)
-    inner_data = "inner: [\n"
+    all_data = f"pub const ALL: [PieceSet; {len(piece_dirs)}] = [\n"
     for piece_set in sorted(piece_dirs):
-        inner_data += f"    {to_pascal_case(piece_set)},\n"
-    inner_data += "]"
-    print(inner_data)
+        all_data += f"    PieceSet::{to_pascal_case(piece_set)},\n"
+    all_data += "];"
+    print(all_data)
+
+    print("#" * 80)
+    print(f"inner: [T; {len(piece_dirs)}],")
 
     print("#" * 80)
     colors = ["blue", "brown", "green", "ic", "pink", "purple"]